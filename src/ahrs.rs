@@ -0,0 +1,438 @@
+// Copyright 2025 Au-Zone Technologies Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Host-side orientation filters.
+//!
+//! The filters fuse the calibrated gyroscope, accelerometer and optionally
+//! magnetometer reports into an orientation in the same ENU reference as the
+//! BNO08x rotation vector, so either source can be published through the
+//! same pipeline. Without the magnetometer the yaw is only integrated from
+//! the gyroscope and starts at zero.
+use crate::{
+    args::Args,
+    math::{Quat, Vec3},
+};
+use clap::ValueEnum;
+use std::time::Instant;
+
+/// Upward reference direction measured by the accelerometer at rest.
+const UP: Vec3 = Vec3::new(0.0, 0.0, 1.0);
+
+/// Updates further apart than this are treated as a restart and only
+/// reinitialize the filter.
+const MAX_DT: f64 = 0.5;
+
+/// Orientation filter run on the host.
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FusionAlgorithm {
+    /// Publish only the on-chip rotation vector.
+    #[default]
+    Off,
+    /// Gradient descent filter with a single gain (`--fusion-beta`).
+    Madgwick,
+    /// Nonlinear complementary filter with proportional and integral gains
+    /// (`--fusion-kp`, `--fusion-ki`).
+    Mahony,
+    /// Quaternion extended Kalman filter with gyroscope process noise and
+    /// accelerometer/magnetometer measurement noise.
+    Ekf,
+}
+
+pub struct Ahrs {
+    algorithm: FusionAlgorithm,
+    beta: f64,
+    kp: f64,
+    ki: f64,
+    gyro_noise: f64,
+    accel_noise: f64,
+    mag_noise: f64,
+
+    orientation: Option<Quat>,
+    /// Mahony integral term in rad/s.
+    integral: Vec3,
+    /// EKF state covariance.
+    covariance: [[f64; 4]; 4],
+    last_update: Option<Instant>,
+}
+
+impl Ahrs {
+    pub fn new(args: &Args) -> Self {
+        Self {
+            algorithm: args.fusion,
+            beta: args.fusion_beta,
+            kp: args.fusion_kp,
+            ki: args.fusion_ki,
+            gyro_noise: args.fusion_gyro_noise,
+            accel_noise: args.fusion_accel_noise,
+            mag_noise: args.fusion_mag_noise,
+            orientation: None,
+            integral: Vec3::default(),
+            covariance: [[0.0; 4]; 4],
+            last_update: None,
+        }
+    }
+
+    /// Fuses one set of readings, timed by the host clock, and returns the
+    /// orientation. `gyro` is in rad/s, `accel` in m/s² and `mag` in µT, all
+    /// in the sensor frame.
+    pub fn update(&mut self, gyro: Vec3, accel: Vec3, mag: Option<Vec3>) -> Option<Quat> {
+        let now = Instant::now();
+        let dt = self
+            .last_update
+            .map(|t| now.duration_since(t).as_secs_f64());
+        self.last_update = Some(now);
+        self.step(gyro, accel, mag, dt)
+    }
+
+    /// Advances the filter by `dt` seconds, or initializes it from the
+    /// accelerometer and magnetometer when `dt` is `None` or too long.
+    fn step(
+        &mut self,
+        gyro: Vec3,
+        accel: Vec3,
+        mag: Option<Vec3>,
+        dt: Option<f64>,
+    ) -> Option<Quat> {
+        let accel = accel.normalize();
+        let mag = mag.and_then(|m| m.normalize());
+
+        let q = match (self.orientation, dt) {
+            (Some(q), Some(dt)) if dt > 0.0 && dt < MAX_DT => q,
+            // Repeated stamp, nothing to integrate.
+            (Some(q), Some(dt)) if dt <= 0.0 => return Some(q),
+            _ => {
+                self.orientation = accel.map(|a| initial_orientation(a, mag));
+                self.integral = Vec3::default();
+                self.covariance = identity().map(|row| row.map(|v| v * 1e-2));
+                return self.orientation;
+            }
+        };
+        let dt = dt.unwrap_or_default();
+
+        let q = match self.algorithm {
+            FusionAlgorithm::Off => q,
+            FusionAlgorithm::Madgwick => self.madgwick(q, gyro, accel, mag, dt),
+            FusionAlgorithm::Mahony => self.mahony(q, gyro, accel, mag, dt),
+            FusionAlgorithm::Ekf => self.ekf(q, gyro, accel, mag, dt),
+        };
+        self.orientation = Some(q.normalize());
+        self.orientation
+    }
+
+    fn madgwick(
+        &self,
+        q: Quat,
+        gyro: Vec3,
+        accel: Option<Vec3>,
+        mag: Option<Vec3>,
+        dt: f64,
+    ) -> Quat {
+        let rate = q * Quat::new(0.0, gyro.x, gyro.y, gyro.z);
+        let mut qdot = [rate.w * 0.5, rate.x * 0.5, rate.y * 0.5, rate.z * 0.5];
+
+        if let Some(accel) = accel {
+            let mut gradient = objective_gradient(&q, UP, accel);
+            if let Some(mag) = mag {
+                let g = objective_gradient(&q, mag_reference(&q, mag), mag);
+                for (a, b) in gradient.iter_mut().zip(g) {
+                    *a += b;
+                }
+            }
+            let norm = gradient.iter().map(|v| v * v).sum::<f64>().sqrt();
+            if norm > 0.0 {
+                for (d, g) in qdot.iter_mut().zip(gradient) {
+                    *d -= self.beta * g / norm;
+                }
+            }
+        }
+
+        Quat::new(
+            q.w + qdot[0] * dt,
+            q.x + qdot[1] * dt,
+            q.y + qdot[2] * dt,
+            q.z + qdot[3] * dt,
+        )
+    }
+
+    fn mahony(
+        &mut self,
+        q: Quat,
+        gyro: Vec3,
+        accel: Option<Vec3>,
+        mag: Option<Vec3>,
+        dt: f64,
+    ) -> Quat {
+        let mut rate = gyro;
+        if let Some(accel) = accel {
+            let inverse = q.conjugate();
+            let up = inverse.rotate(UP);
+            let mut error = accel.cross(&up);
+            if let Some(mag) = mag {
+                // Only the component about the vertical, so the
+                // magnetometer cannot disturb roll and pitch.
+                let heading = mag.cross(&inverse.rotate(mag_reference(&q, mag)));
+                error = error + up.scale(heading.dot(&up));
+            }
+            if self.ki > 0.0 {
+                self.integral = self.integral + error.scale(self.ki * dt);
+            }
+            rate = rate + self.integral + error.scale(self.kp);
+        }
+        q * Quat::from_rotation_vector(rate.scale(dt))
+    }
+
+    fn ekf(
+        &mut self,
+        q: Quat,
+        gyro: Vec3,
+        accel: Option<Vec3>,
+        mag: Option<Vec3>,
+        dt: f64,
+    ) -> Quat {
+        // Predict: q' = q ⊗ dq is linear in q.
+        let dq = Quat::from_rotation_vector(gyro.scale(dt));
+        let f = [
+            [dq.w, -dq.x, -dq.y, -dq.z],
+            [dq.x, dq.w, dq.z, -dq.y],
+            [dq.y, -dq.z, dq.w, dq.x],
+            [dq.z, dq.y, -dq.x, dq.w],
+        ];
+        let mut x = q * dq;
+        // Gyroscope noise enters through dq/dω = ½ Ξ(q) dt.
+        let xi = [
+            [-q.x, -q.y, -q.z],
+            [q.w, -q.z, q.y],
+            [q.z, q.w, -q.x],
+            [-q.y, q.x, q.w],
+        ];
+        let noise = (self.gyro_noise * dt * 0.5).powi(2);
+        let p = &self.covariance;
+        let predicted = std::array::from_fn(|i| {
+            std::array::from_fn(|j| {
+                let fpf: f64 = (0..4)
+                    .flat_map(|k| (0..4).map(move |l| (k, l)))
+                    .map(|(k, l)| f[i][k] * p[k][l] * f[j][l])
+                    .sum();
+                let q: f64 = xi[i].iter().zip(&xi[j]).map(|(a, b)| a * b).sum();
+                fpf + noise * q
+            })
+        });
+        self.covariance = predicted;
+
+        // Correct with each measured direction as a whole, so the three
+        // components share one linearization and gain.
+        let mut measurements = Vec::with_capacity(2);
+        if let Some(accel) = accel {
+            measurements.push((UP, accel, self.accel_noise));
+        }
+        if let (Some(mag), Some(_)) = (mag, accel) {
+            measurements.push((mag_reference(&x, mag), mag, self.mag_noise));
+        }
+        for (reference, measured, sigma) in measurements {
+            let (predicted, h) = body_direction(&x, reference);
+            let residual = measured - predicted;
+            let residual = [residual.x, residual.y, residual.z];
+            let p = &mut self.covariance;
+            // P Hᵀ and S = H P Hᵀ + σ² I.
+            let pht: [[f64; 3]; 4] = std::array::from_fn(|i| {
+                std::array::from_fn(|m| (0..4).map(|j| p[i][j] * h[m][j]).sum())
+            });
+            let s: [[f64; 3]; 3] = std::array::from_fn(|m| {
+                std::array::from_fn(|n| {
+                    let hph: f64 = (0..4).map(|i| h[m][i] * pht[i][n]).sum();
+                    hph + if m == n { sigma * sigma } else { 0.0 }
+                })
+            });
+            let Some(s_inv) = invert3(&s) else {
+                continue;
+            };
+            let k: [[f64; 3]; 4] = std::array::from_fn(|i| {
+                std::array::from_fn(|n| (0..3).map(|m| pht[i][m] * s_inv[m][n]).sum())
+            });
+            let dx: [f64; 4] = std::array::from_fn(|i| (0..3).map(|m| k[i][m] * residual[m]).sum());
+            x = Quat::new(x.w + dx[0], x.x + dx[1], x.y + dx[2], x.z + dx[3]);
+            // P -= K H P, where H P is the transpose of P Hᵀ.
+            let correction: [[f64; 4]; 4] = std::array::from_fn(|i| {
+                std::array::from_fn(|j| (0..3).map(|m| k[i][m] * pht[j][m]).sum())
+            });
+            for i in 0..4 {
+                for j in 0..4 {
+                    p[i][j] -= correction[i][j];
+                }
+            }
+        }
+        x
+    }
+}
+
+fn identity() -> [[f64; 4]; 4] {
+    std::array::from_fn(|i| std::array::from_fn(|j| if i == j { 1.0 } else { 0.0 }))
+}
+
+/// Inverse of a 3×3 matrix from its adjugate, or `None` when singular.
+fn invert3(m: &[[f64; 3]; 3]) -> Option<[[f64; 3]; 3]> {
+    let cofactor = |i: usize, j: usize| {
+        let (r0, r1) = ((i + 1) % 3, (i + 2) % 3);
+        let (c0, c1) = ((j + 1) % 3, (j + 2) % 3);
+        m[r0][c0] * m[r1][c1] - m[r0][c1] * m[r1][c0]
+    };
+    let det: f64 = (0..3).map(|j| m[0][j] * cofactor(0, j)).sum();
+    if !det.is_normal() {
+        return None;
+    }
+    Some(std::array::from_fn(|i| {
+        std::array::from_fn(|j| cofactor(j, i) / det)
+    }))
+}
+
+/// Orientation from a single accelerometer and optional magnetometer
+/// reading, with zero yaw when the magnetometer is not used.
+fn initial_orientation(accel: Vec3, mag: Option<Vec3>) -> Quat {
+    let east = mag.and_then(|m| m.cross(&accel).normalize());
+    match east {
+        Some(east) => {
+            // Rows of the body to world rotation are the world axes
+            // expressed in the body frame.
+            let north = accel.cross(&east);
+            Quat::from_matrix(&[
+                [east.x, east.y, east.z],
+                [north.x, north.y, north.z],
+                [accel.x, accel.y, accel.z],
+            ])
+        }
+        None => {
+            let roll = accel.y.atan2(accel.z);
+            let pitch = (-accel.x).atan2(accel.y.hypot(accel.z));
+            Quat::from_rpy(roll, pitch, 0.0)
+        }
+    }
+}
+
+/// Earth frame magnetic reference for a normalized body frame reading: the
+/// measured field rotated into the world frame with its horizontal part
+/// pointing north, so the magnetometer only corrects yaw.
+fn mag_reference(q: &Quat, mag: Vec3) -> Vec3 {
+    let world = q.rotate(mag);
+    Vec3::new(0.0, world.x.hypot(world.y), world.z)
+}
+
+/// World direction `d` seen in the body frame, `R(q)ᵀ d`, and its Jacobian
+/// with respect to `(w, x, y, z)`.
+fn body_direction(q: &Quat, d: Vec3) -> (Vec3, [[f64; 4]; 3]) {
+    let Quat { w, x, y, z } = *q;
+    let ex = [[w, x, -y, -z], [-z, y, x, -w], [y, z, w, x]];
+    let ey = [[z, y, x, w], [w, -x, y, -z], [-x, -w, z, y]];
+    let ez = [[-y, z, -w, x], [x, w, z, y], [w, -x, -y, z]];
+    let jacobian = std::array::from_fn(|i| {
+        std::array::from_fn(|j| 2.0 * (d.x * ex[i][j] + d.y * ey[i][j] + d.z * ez[i][j]))
+    });
+    (q.conjugate().rotate(d), jacobian)
+}
+
+/// Gradient of `½|R(q)ᵀ d - s|²` with respect to the quaternion.
+fn objective_gradient(q: &Quat, d: Vec3, s: Vec3) -> [f64; 4] {
+    let (h, jacobian) = body_direction(q, d);
+    let f = h - s;
+    let f = [f.x, f.y, f.z];
+    std::array::from_fn(|j| (0..3).map(|i| jacobian[i][j] * f[i]).sum())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+
+    /// Rotation angle in radians between two orientations.
+    fn angle(a: Quat, b: Quat) -> f64 {
+        let d = (a.conjugate() * b).normalize();
+        2.0 * d.w.abs().min(1.0).acos()
+    }
+
+    #[test]
+    fn test_converges_to_tilted_heading() {
+        let truth = Quat::from_rpy(0.3, -0.2, 0.5);
+        let inverse = truth.conjugate();
+        let accel = inverse.rotate(Vec3::new(0.0, 0.0, 9.81));
+        let mag = inverse.rotate(Vec3::new(0.0, 25.0, -40.0));
+
+        for algorithm in ["madgwick", "mahony", "ekf"] {
+            let args = Args::parse_from(["edgefirst-imu", "--fusion", algorithm]);
+            let mut ahrs = Ahrs::new(&args);
+            // Start level and facing east, then hold the true attitude.
+            ahrs.step(Vec3::default(), Vec3::new(0.0, 0.0, 9.81), None, None);
+            let mut q = None;
+            for _ in 0..4000 {
+                q = ahrs.step(Vec3::default(), accel, Some(mag), Some(0.005));
+            }
+            let error = angle(q.unwrap(), truth);
+            assert!(error < 0.5f64.to_radians(), "{algorithm}: {error}");
+        }
+    }
+
+    #[test]
+    fn test_invert3() {
+        let m = [[2.0, 1.0, 0.0], [1.0, 3.0, 1.0], [0.0, 1.0, 4.0]];
+        let inverse = invert3(&m).unwrap();
+        for (i, row) in m.iter().enumerate() {
+            for j in 0..3 {
+                let v: f64 = row.iter().zip(&inverse).map(|(a, b)| a * b[j]).sum();
+                assert!((v - if i == j { 1.0 } else { 0.0 }).abs() < 1e-12);
+            }
+        }
+        assert!(invert3(&[[1.0, 2.0, 3.0], [2.0, 4.0, 6.0], [0.0, 0.0, 1.0]]).is_none());
+    }
+
+    #[test]
+    fn test_integrates_gyro_yaw() {
+        let args = Args::parse_from(["edgefirst-imu", "--fusion", "mahony"]);
+        let mut ahrs = Ahrs::new(&args);
+        let up = Vec3::new(0.0, 0.0, 9.81);
+        ahrs.step(Vec3::default(), up, None, None);
+        let mut q = None;
+        for _ in 0..200 {
+            q = ahrs.step(Vec3::new(0.0, 0.0, 0.5), up, None, Some(0.005));
+        }
+        // One second at 0.5 rad/s about the vertical.
+        let error = angle(q.unwrap(), Quat::from_rpy(0.0, 0.0, 0.5));
+        assert!(error < 1e-6, "{error}");
+    }
+
+    #[test]
+    fn test_reinitializes_after_gap() {
+        let args = Args::parse_from(["edgefirst-imu", "--fusion", "mahony"]);
+        let mut ahrs = Ahrs::new(&args);
+        let up = Vec3::new(0.0, 0.0, 9.81);
+        ahrs.step(Vec3::default(), up, None, None);
+        for _ in 0..200 {
+            ahrs.step(Vec3::new(0.0, 0.0, 0.5), up, None, Some(0.005));
+        }
+        // After a pause the filter restarts from the accelerometer, with
+        // the yaw at zero, instead of holding the stale orientation.
+        let tilted = Quat::from_rpy(0.4, 0.0, 0.0);
+        let accel = tilted.conjugate().rotate(up);
+        let q = ahrs.step(Vec3::default(), accel, None, Some(1.0));
+        let error = angle(q.unwrap(), tilted);
+        assert!(error < 1e-9, "{error}");
+        assert_eq!(ahrs.integral, Vec3::default());
+        // A repeated stamp leaves the orientation unchanged.
+        assert_eq!(ahrs.step(Vec3::new(1.0, 0.0, 0.0), up, None, Some(0.0)), q);
+    }
+
+    #[test]
+    fn test_ekf_confident_update() {
+        // With a confident measurement a single correction lands close to
+        // the measured attitude.
+        let args = Args::parse_from(["edgefirst-imu", "--fusion", "ekf"]);
+        let mut ahrs = Ahrs::new(&args);
+        ahrs.accel_noise = 1e-3;
+        ahrs.covariance = identity();
+        let truth = Quat::from_rpy(0.9, 0.0, 0.0);
+        let accel = truth.conjugate().rotate(UP);
+        let start = Quat::from_rpy(0.6, 0.0, 0.0);
+        let q = ahrs
+            .ekf(start, Vec3::default(), Some(accel), None, 0.0)
+            .normalize();
+        let error = angle(q, truth);
+        assert!(error < 0.02, "{error}");
+    }
+}
@@ -0,0 +1,325 @@
+// Copyright 2025 Au-Zone Technologies Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Tilt, tip-over, free-fall and impact alarms.
+//!
+//! Each alarm latches when its condition is first met and stays latched
+//! after the condition clears, until it is cleared by a query. The samples
+//! around each alarm are kept in memory: the pre-event window from a ring
+//! buffer of recent samples and the post-event window collected after the
+//! alarm is raised. The latest completed event of each alarm is kept for
+//! retrieval.
+use crate::{
+    args::Args,
+    math::Vec3,
+    msgs::{Alarm, AlarmEvent},
+    sample::Sample,
+};
+use edgefirst_schemas::{builtin_interfaces, std_msgs};
+use log::{info, warn};
+use std::collections::VecDeque;
+
+/// Standard gravity in m/s².
+const GRAVITY: f64 = 9.80665;
+
+/// Nanoseconds the acceleration must stay below the free-fall threshold,
+/// about 5 cm of fall, so single low readings do not raise it.
+const FREE_FALL_TIME: u64 = 100_000_000;
+
+/// Nanoseconds between republishing latched alarms.
+const STATUS_INTERVAL: u64 = 1_000_000_000;
+
+const NAMES: [&str; 4] = ["tilt", "tip_over", "free_fall", "impact"];
+
+struct AlarmState {
+    /// Condition is currently met.
+    active: bool,
+    latched: bool,
+    since: builtin_interfaces::Time,
+    value: f64,
+    threshold: f64,
+    /// Event collecting its post-event samples until the stamp.
+    pending: Option<(AlarmEvent, u64)>,
+    event: Option<AlarmEvent>,
+}
+
+pub struct AlarmMonitor {
+    frame_id: String,
+    roll: f64,
+    pitch: f64,
+    tip_over: f64,
+    free_fall: f64,
+    impact: f64,
+    /// Pre and post-event windows in nanoseconds.
+    pre: u64,
+    post: u64,
+
+    alarms: [AlarmState; 4],
+    samples: VecDeque<Sample>,
+    free_fall_start: Option<u64>,
+    last_status: Option<u64>,
+}
+
+impl AlarmMonitor {
+    pub fn new(args: &Args) -> Self {
+        Self {
+            frame_id: args.frame_id.clone(),
+            roll: args.alarm_roll,
+            pitch: args.alarm_pitch,
+            tip_over: args.alarm_tip_over,
+            free_fall: args.alarm_free_fall,
+            impact: args.alarm_impact,
+            pre: (args.alarm_pre * 1e9) as u64,
+            post: (args.alarm_post * 1e9) as u64,
+            alarms: std::array::from_fn(|_| AlarmState {
+                active: false,
+                latched: false,
+                since: builtin_interfaces::Time::new(0, 0),
+                value: 0.0,
+                threshold: 0.0,
+                pending: None,
+                event: None,
+            }),
+            samples: VecDeque::new(),
+            free_fall_start: None,
+            last_status: None,
+        }
+    }
+
+    /// Checks an output frame sample and returns the alarms to publish:
+    /// those raised or deactivated by this sample, and every latched alarm
+    /// once per second.
+    pub fn update(&mut self, sample: &Sample) -> Vec<Alarm> {
+        let stamp = sample.stamp.to_nanos();
+        self.samples.push_back(sample.clone());
+        while self
+            .samples
+            .front()
+            .is_some_and(|s| stamp.saturating_sub(s.stamp.to_nanos()) > self.pre)
+        {
+            self.samples.pop_front();
+        }
+        for alarm in &mut self.alarms {
+            if let Some((event, _)) = &mut alarm.pending {
+                event.samples.push(sample.to_batch_sample());
+            }
+            if alarm.pending.as_ref().is_some_and(|(_, end)| stamp >= *end) {
+                alarm.event = alarm.pending.take().map(|(event, _)| event);
+            }
+        }
+
+        // Body frame representation of the world vertical, which is the
+        // body vertical when level in both conventions.
+        let vertical = sample
+            .orientation
+            .conjugate()
+            .rotate(Vec3::new(0.0, 0.0, 1.0));
+        let roll = vertical.y.atan2(vertical.z).to_degrees().abs();
+        let pitch = (-vertical.x).clamp(-1.0, 1.0).asin().to_degrees().abs();
+        let tilt = vertical.z.clamp(-1.0, 1.0).acos().to_degrees();
+        let g = sample.linear_acceleration.norm() / GRAVITY;
+
+        let falling = g < self.free_fall;
+        let free_fall_start = match (falling, self.free_fall_start) {
+            (true, Some(start)) => Some(start),
+            (true, None) => Some(stamp),
+            (false, _) => None,
+        };
+        self.free_fall_start = free_fall_start;
+
+        let tilt_check = if roll / self.roll >= pitch / self.pitch {
+            (roll, self.roll)
+        } else {
+            (pitch, self.pitch)
+        };
+        let checks = [
+            (tilt_check.0 > tilt_check.1, tilt_check),
+            (tilt > self.tip_over, (tilt, self.tip_over)),
+            (
+                free_fall_start.is_some_and(|start| stamp.saturating_sub(start) >= FREE_FALL_TIME),
+                (g, self.free_fall),
+            ),
+            (g > self.impact, (g, self.impact)),
+        ];
+
+        let mut output = Vec::new();
+        for (index, (active, (value, threshold))) in checks.into_iter().enumerate() {
+            let alarm = &mut self.alarms[index];
+            let was_active = alarm.active;
+            alarm.active = active;
+            if active {
+                alarm.value = value;
+                alarm.threshold = threshold;
+            }
+            if active && !alarm.latched {
+                warn!(
+                    "{} alarm raised: {:.2} exceeds {:.2}",
+                    NAMES[index], value, threshold
+                );
+                alarm.latched = true;
+                alarm.since = sample.stamp.clone();
+                let event = AlarmEvent {
+                    alarm: message(&self.frame_id, index, alarm, &sample.stamp),
+                    samples: self.samples.iter().map(Sample::to_batch_sample).collect(),
+                };
+                alarm.pending = Some((event, stamp + self.post));
+            }
+            if active != was_active {
+                output.push(message(&self.frame_id, index, alarm, &sample.stamp));
+            }
+        }
+
+        if self
+            .last_status
+            .is_none_or(|t| stamp.saturating_sub(t) >= STATUS_INTERVAL)
+        {
+            self.last_status = Some(stamp);
+            for (index, alarm) in self.alarms.iter().enumerate() {
+                if alarm.latched && !output.iter().any(|a| a.alarm as usize == index) {
+                    output.push(message(&self.frame_id, index, alarm, &sample.stamp));
+                }
+            }
+        }
+        output
+    }
+
+    /// Clears the latched alarms selected by `name`, or all when `None`.
+    /// Alarms whose condition is still met stay latched. Returns the state
+    /// of every selected latched alarm after clearing.
+    pub fn clear(&mut self, name: Option<&str>) -> Result<Vec<Alarm>, String> {
+        let selected = select(name)?;
+        let stamp = self
+            .samples
+            .back()
+            .map_or(builtin_interfaces::Time::new(0, 0), |s| s.stamp.clone());
+        let mut output = Vec::new();
+        for (index, alarm) in self.alarms.iter_mut().enumerate() {
+            if !selected.contains(&index) || !alarm.latched {
+                continue;
+            }
+            if alarm.active {
+                info!("{} alarm is still active and stays latched", NAMES[index]);
+            } else {
+                info!("{} alarm cleared", NAMES[index]);
+                alarm.latched = false;
+            }
+            output.push(message(&self.frame_id, index, alarm, &stamp));
+        }
+        if output.is_empty() {
+            return Err("no latched alarm to clear".to_owned());
+        }
+        Ok(output)
+    }
+
+    /// Latest completed event of the alarms selected by `name`, or all
+    /// when `None`.
+    pub fn events(&self, name: Option<&str>) -> Result<Vec<AlarmEvent>, String> {
+        let selected = select(name)?;
+        let events: Vec<_> = selected
+            .into_iter()
+            .filter_map(|index| self.alarms[index].event.clone())
+            .collect();
+        if events.is_empty() {
+            return Err("no alarm event recorded".to_owned());
+        }
+        Ok(events)
+    }
+}
+
+/// Indices of the alarms matching the `alarm` query parameter.
+fn select(name: Option<&str>) -> Result<Vec<usize>, String> {
+    match name {
+        None => Ok((0..NAMES.len()).collect()),
+        Some(name) => NAMES
+            .iter()
+            .position(|n| *n == name)
+            .map(|index| vec![index])
+            .ok_or_else(|| format!("unknown alarm {name:?}, expected one of {NAMES:?}")),
+    }
+}
+
+fn message(
+    frame_id: &str,
+    index: usize,
+    alarm: &AlarmState,
+    stamp: &builtin_interfaces::Time,
+) -> Alarm {
+    Alarm {
+        header: std_msgs::Header {
+            stamp: stamp.clone(),
+            frame_id: frame_id.to_owned(),
+        },
+        alarm: index as u8,
+        severity: if index == Alarm::TILT as usize {
+            Alarm::WARNING
+        } else {
+            Alarm::CRITICAL
+        },
+        active: alarm.active,
+        latched: alarm.latched,
+        since: alarm.since.clone(),
+        value: alarm.value,
+        threshold: alarm.threshold,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::math::Quat;
+    use clap::Parser;
+
+    #[test]
+    fn test_impact_latches_with_event_window() {
+        let args = Args::parse_from(["edgefirst-imu", "--alarms"]);
+        let mut monitor = AlarmMonitor::new(&args);
+        let mut sample = Sample {
+            stamp: builtin_interfaces::Time::new(0, 0),
+            orientation: Quat::new(1.0, 0.0, 0.0, 0.0),
+            angular_velocity: Vec3::default(),
+            linear_acceleration: Vec3::new(0.0, 0.0, GRAVITY),
+            heading_accuracy: 0.0,
+        };
+        let mut raised = Vec::new();
+        for i in 0..1000u64 {
+            sample.stamp = builtin_interfaces::Time::from_nanos(1_000_000_000 + i * 5_000_000);
+            sample.linear_acceleration.x = if i == 400 { 50.0 } else { 0.0 };
+            raised.extend(monitor.update(&sample));
+        }
+        let impact = raised
+            .iter()
+            .find(|a| a.alarm == Alarm::IMPACT && a.active)
+            .unwrap();
+        assert_eq!(impact.severity, Alarm::CRITICAL);
+        assert!(raised.iter().all(|a| a.alarm == Alarm::IMPACT));
+        // Still latched after the condition cleared.
+        assert!(raised.last().unwrap().latched);
+
+        // One second before and after the impact at 5 ms.
+        let event = &monitor.events(Some("impact")).unwrap()[0];
+        assert_eq!(event.samples.len(), 401);
+        assert!(monitor.events(Some("tilt")).is_err());
+
+        let cleared = monitor.clear(None).unwrap();
+        assert!(!cleared[0].latched);
+        assert!(monitor.clear(None).is_err());
+        assert!(monitor.clear(Some("bump")).is_err());
+    }
+
+    #[test]
+    fn test_free_fall_with_stamp_going_backwards() {
+        let args = Args::parse_from(["edgefirst-imu", "--alarms"]);
+        let mut monitor = AlarmMonitor::new(&args);
+        let mut sample = Sample {
+            stamp: builtin_interfaces::Time::new(10, 0),
+            orientation: Quat::new(1.0, 0.0, 0.0, 0.0),
+            angular_velocity: Vec3::default(),
+            linear_acceleration: Vec3::default(),
+            heading_accuracy: 0.0,
+        };
+        monitor.update(&sample);
+        // A clock step back during the fall does not raise the alarm early.
+        sample.stamp = builtin_interfaces::Time::new(9, 0);
+        assert!(monitor.update(&sample).is_empty());
+    }
+}
@@ -1,8 +1,29 @@
 // Copyright 2025 Au-Zone Technologies Inc.
 // SPDX-License-Identifier: Apache-2.0
 
-use clap::Parser;
+use crate::{
+    ahrs::FusionAlgorithm,
+    characterize::CharacterizeArgs,
+    convention::Convention,
+    decimate::OutputSpec,
+    delta,
+    euler::EulerOrder,
+    filter::FilterSpec,
+    frs::{FrsDumpArgs, FrsRestoreArgs},
+    heading::HeadingSource,
+    mounting::Mounting,
+    mounting::SensorOrientation,
+    tare::TareArgs,
+    tare::TareBackend,
+    vibration,
+    vibration::Band,
+    wheel::WheelMode,
+    wheel::WheelSource,
+    wmm,
+};
+use clap::{Parser, Subcommand};
 use serde_json::json;
+use std::path::PathBuf;
 use tracing::level_filters::LevelFilter;
 use zenoh::config::{Config, WhatAmI};
 
@@ -43,14 +64,539 @@ pub struct Args {
     #[arg(long, default_value = "IMU_RST")]
     pub reset: String,
 
-    /// Apply the Maivin2 FRS Configuration.
+    /// Write the --configure-orientation sensor orientation FRS record and
+    /// exit.
     #[arg(long)]
     pub configure: bool,
 
+    /// Sensor orientation written by --configure: a preset (maivin2,
+    /// raivin, identity), quat:x,y,z,w, rpy:roll,pitch,yaw in degrees or
+    /// matrix:r00,...,r22.
+    #[arg(long, env = "CONFIGURE_ORIENTATION", default_value = "maivin2")]
+    pub configure_orientation: SensorOrientation,
+
+    /// With --configure or frs-restore, show the record without writing
+    /// it.
+    #[arg(long)]
+    pub dry_run: bool,
+
+    /// Runs a one-off command instead of the service.
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
     /// ros topic.
     #[arg(long, default_value = "rt/imu")]
     pub topic: String,
 
+    /// Rotation vector report interval in milliseconds. Every report is
+    /// processed, so this is the sample rate of the service.
+    #[arg(long, env = "SAMPLE_INTERVAL", default_value = "5", value_parser = clap::value_parser!(u64).range(1..=1000))]
+    pub sample_interval: u64,
+
+    /// Accelerometer report interval in milliseconds.
+    #[arg(long, env = "ACCEL_INTERVAL", default_value = "20", value_parser = clap::value_parser!(u64).range(1..=1000))]
+    pub accel_interval: u64,
+
+    /// Gyroscope report interval in milliseconds.
+    #[arg(long, env = "GYRO_INTERVAL", default_value = "20", value_parser = clap::value_parser!(u64).range(1..=1000))]
+    pub gyro_interval: u64,
+
+    /// Publish rate of --topic in Hz, averaging the samples in between. Zero
+    /// publishes every sample.
+    #[arg(long, env = "RATE", default_value = "0")]
+    pub rate: f64,
+
+    /// Additional IMU outputs as topic@rate, e.g. rt/imu/telemetry@10. Can be
+    /// given several times or comma separated.
+    #[arg(long, env = "OUTPUT", value_delimiter = ',')]
+    pub output: Vec<OutputSpec>,
+
+    /// Also publish the samples in batches on --batch-topic, one message per
+    /// --batch-size samples or --batch-deadline.
+    #[arg(long, env = "BATCH")]
+    pub batch: bool,
+
+    /// ros topic for batched IMU samples.
+    #[arg(long, default_value = "rt/imu/batch")]
+    pub batch_topic: String,
+
+    /// Number of samples per batch.
+    #[arg(long, env = "BATCH_SIZE", default_value = "20", value_parser = clap::value_parser!(u64).range(1..))]
+    pub batch_size: u64,
+
+    /// Milliseconds after the first sample of a batch at which it is
+    /// flushed even if it is not full.
+    #[arg(long, env = "BATCH_DEADLINE", default_value = "100")]
+    pub batch_deadline: u64,
+
+    /// With --batch, do not publish the individual samples on --topic.
+    /// The other outputs are unaffected.
+    #[arg(long, env = "BATCH_ONLY")]
+    pub batch_only: bool,
+
+    /// Publish coning and sculling compensated delta-angle and
+    /// delta-velocity increments for inertial navigation consumers.
+    #[arg(long, env = "DELTA")]
+    pub delta: bool,
+
+    /// ros topic for the delta-angle and delta-velocity increments.
+    #[arg(long, default_value = "rt/imu/delta")]
+    pub delta_topic: String,
+
+    /// Rate in Hz at which increments are published, each integrated over
+    /// all samples since the previous one.
+    #[arg(long, env = "DELTA_RATE", default_value = "50", value_parser = delta::rate)]
+    pub delta_rate: f64,
+
+    /// Topic whose message stamps mark camera frames, such as the camera
+    /// info. IMU samples are preintegrated between consecutive frames and
+    /// published on --preintegrated-topic. Empty disables preintegration.
+    #[arg(long, env = "FRAME_TOPIC", default_value = "")]
+    pub frame_topic: String,
+
+    /// ros topic for the preintegrated IMU between camera frames.
+    #[arg(long, default_value = "rt/imu/preintegrated")]
+    pub preintegrated_topic: String,
+
+    /// Answer ego-rotation queries on --rotation-key with the rotation and
+    /// mean angular velocity over a requested interval, for motion
+    /// compensation of scans and rolling-shutter frames.
+    #[arg(long, env = "ROTATION_QUERY")]
+    pub rotation_query: bool,
+
+    /// Key expression of the ego-rotation queryable.
+    #[arg(long, default_value = "rt/imu/rotation")]
+    pub rotation_key: String,
+
+    /// Seconds of angular velocity history kept for ego-rotation queries.
+    #[arg(long, env = "ROTATION_HISTORY", default_value = "10")]
+    pub rotation_history: f64,
+
+    /// Frame ID written to the header of published messages.
+    #[arg(long, env = "FRAME_ID", default_value = "imu_link")]
+    pub frame_id: String,
+
+    /// Namespace prefixed to all published topics (e.g. robot1 publishes
+    /// robot1/rt/imu). Leading and trailing slashes on the namespace and
+    /// the topics are dropped, as Zenoh keys cannot start or end with one,
+    /// so /rt/imu is namespaced like rt/imu.
+    #[arg(long, env = "NAMESPACE", default_value = "")]
+    pub namespace: String,
+
+    /// Broadcast the orientation as a tf2 transform from --tf-parent-frame
+    /// to --frame-id.
+    #[arg(long, env = "TF")]
+    pub tf: bool,
+
+    /// Parent frame of the broadcast orientation transform.
+    #[arg(long, env = "TF_PARENT_FRAME", default_value = "odom_level")]
+    pub tf_parent_frame: String,
+
+    /// ros topic for tf2 transforms.
+    #[arg(long, default_value = "rt/tf")]
+    pub tf_topic: String,
+
+    /// Publish roll/pitch/yaw and compass heading in degrees.
+    #[arg(long, env = "EULER")]
+    pub euler: bool,
+
+    /// Intrinsic rotation order used for the Euler angle topic.
+    #[arg(long, env = "EULER_ORDER", value_enum, default_value_t = EulerOrder::Zyx)]
+    pub euler_order: EulerOrder,
+
+    /// ros topic for Euler angles and heading.
+    #[arg(long, default_value = "rt/imu/euler")]
+    pub euler_topic: String,
+
+    /// Magnetic declination in degrees (east positive) used to compute the
+    /// true heading when no position is known.
+    #[arg(
+        long,
+        env = "DECLINATION",
+        default_value = "0.0",
+        allow_hyphen_values = true
+    )]
+    pub declination: f64,
+
+    /// Latitude in degrees used to compute the declination from the World
+    /// Magnetic Model until a NavSatFix is received.
+    #[arg(long, env = "LATITUDE", allow_hyphen_values = true)]
+    pub latitude: Option<f64>,
+
+    /// Longitude in degrees used to compute the declination from the World
+    /// Magnetic Model until a NavSatFix is received.
+    #[arg(long, env = "LONGITUDE", allow_hyphen_values = true)]
+    pub longitude: Option<f64>,
+
+    /// Altitude in metres above the WGS84 ellipsoid for --latitude and
+    /// --longitude.
+    #[arg(
+        long,
+        env = "ALTITUDE",
+        default_value = "0.0",
+        allow_hyphen_values = true
+    )]
+    pub altitude: f64,
+
+    /// Date (YYYY-MM-DD) at which the World Magnetic Model is evaluated.
+    /// Defaults to the current system date.
+    #[arg(long, env = "MAGNETIC_DATE", value_parser = wmm::parse_date)]
+    pub magnetic_date: Option<f64>,
+
+    /// sensor_msgs/NavSatFix topic used to update the declination. Empty
+    /// disables the subscription.
+    #[arg(long, env = "NAVSAT_TOPIC", default_value = "")]
+    pub navsat_topic: String,
+
+    /// Reference the published orientation to true north by rotating it
+    /// by the declination.
+    #[arg(long, env = "TRUE_NORTH")]
+    pub true_north: bool,
+
+    /// Monitor the magnetic field for disturbances and publish its status.
+    #[arg(long, env = "MAG_MONITOR")]
+    pub mag_monitor: bool,
+
+    /// ros topic for the magnetic disturbance status.
+    #[arg(long, default_value = "rt/imu/magnetic")]
+    pub mag_topic: String,
+
+    /// Field strength deviation from the expected value, in percent, that
+    /// flags a disturbance.
+    #[arg(long, env = "MAG_INTENSITY_TOLERANCE", default_value = "15.0")]
+    pub mag_intensity_tolerance: f64,
+
+    /// Dip angle deviation from the expected value, in degrees, that flags
+    /// a disturbance.
+    #[arg(long, env = "MAG_INCLINATION_TOLERANCE", default_value = "10.0")]
+    pub mag_inclination_tolerance: f64,
+
+    /// Heading accuracy estimate, in degrees, above which the field is
+    /// considered disturbed.
+    #[arg(long, env = "MAG_ACCURACY_LIMIT", default_value = "30.0")]
+    pub mag_accuracy_limit: f64,
+
+    /// Seconds the field must stay clean before a disturbance clears.
+    #[arg(long, env = "MAG_CLEAR_TIME", default_value = "2.0")]
+    pub mag_clear_time: f64,
+
+    /// Publish the game rotation vector, aligned to the last clean heading,
+    /// while the field is disturbed.
+    #[arg(long, env = "MAG_FALLBACK")]
+    pub mag_fallback: bool,
+
+    /// Host-side orientation filter fed with the calibrated accelerometer,
+    /// gyroscope and optionally magnetometer reports.
+    #[arg(long, env = "FUSION", value_enum, default_value_t = FusionAlgorithm::Off)]
+    pub fusion: FusionAlgorithm,
+
+    /// ros topic for the IMU message carrying the host-side orientation,
+    /// published alongside --topic for comparison.
+    #[arg(long, default_value = "rt/imu/fusion")]
+    pub fusion_topic: String,
+
+    /// Publish the host-side orientation on --topic in place of the
+    /// on-chip rotation vector.
+    #[arg(long, env = "FUSION_PRIMARY")]
+    pub fusion_primary: bool,
+
+    /// Use the magnetometer in the host-side filter. Without it the yaw is
+    /// integrated from the gyroscope only.
+    #[arg(long, env = "FUSION_MAG")]
+    pub fusion_mag: bool,
+
+    /// Madgwick filter gain in rad/s.
+    #[arg(long, env = "FUSION_BETA", default_value = "0.1")]
+    pub fusion_beta: f64,
+
+    /// Mahony filter proportional gain.
+    #[arg(long, env = "FUSION_KP", default_value = "1.0")]
+    pub fusion_kp: f64,
+
+    /// Mahony filter integral gain, estimating the gyroscope bias.
+    #[arg(long, env = "FUSION_KI", default_value = "0.0")]
+    pub fusion_ki: f64,
+
+    /// EKF gyroscope noise density in rad/s.
+    #[arg(long, env = "FUSION_GYRO_NOISE", default_value = "0.01")]
+    pub fusion_gyro_noise: f64,
+
+    /// EKF noise of the normalized accelerometer direction.
+    #[arg(long, env = "FUSION_ACCEL_NOISE", default_value = "0.05")]
+    pub fusion_accel_noise: f64,
+
+    /// EKF noise of the normalized magnetometer direction.
+    #[arg(long, env = "FUSION_MAG_NOISE", default_value = "0.1")]
+    pub fusion_mag_noise: f64,
+
+    /// Topic providing an external heading (e.g. GNSS course over ground or
+    /// dual-antenna heading) blended into the published yaw. Empty disables
+    /// the subscription.
+    #[arg(long, env = "HEADING_TOPIC", default_value = "")]
+    pub heading_topic: String,
+
+    /// Message type of --heading-topic.
+    #[arg(long, env = "HEADING_SOURCE", value_enum, default_value_t = HeadingSource::Velocity)]
+    pub heading_source: HeadingSource,
+
+    /// Standard deviation of the external heading in degrees. Lower values
+    /// trust it more.
+    #[arg(long, env = "HEADING_NOISE", default_value = "5.0")]
+    pub heading_noise: f64,
+
+    /// Drift of the IMU heading relative to the external heading, in
+    /// degrees per square root second.
+    #[arg(long, env = "HEADING_DRIFT", default_value = "0.5")]
+    pub heading_drift: f64,
+
+    /// Seconds without an external heading after which the correction is
+    /// held and the next heading is applied directly.
+    #[arg(long, env = "HEADING_TIMEOUT", default_value = "5.0")]
+    pub heading_timeout: f64,
+
+    /// Minimum ground speed in m/s for the course over ground to be used as
+    /// the heading.
+    #[arg(long, env = "HEADING_MIN_SPEED", default_value = "1.0")]
+    pub heading_min_speed: f64,
+
+    /// Maximum time in seconds between an external heading and the IMU
+    /// heading it is matched with. Headings without a match are ignored.
+    #[arg(long, env = "HEADING_MAX_SKEW", default_value = "0.1")]
+    pub heading_max_skew: f64,
+
+    /// Detect when the body is stationary and estimate the residual
+    /// gyroscope bias while it is.
+    #[arg(long, env = "STATIONARY")]
+    pub stationary: bool,
+
+    /// ros topic for the stationary state and gyroscope bias.
+    #[arg(long, default_value = "rt/imu/stationary")]
+    pub stationary_topic: String,
+
+    /// Length in seconds of the window the stationary detector evaluates.
+    #[arg(long, env = "STATIONARY_WINDOW", default_value = "0.5")]
+    pub stationary_window: f64,
+
+    /// Acceleration standard deviation in m/s² below which the body may be
+    /// stationary.
+    #[arg(long, env = "STATIONARY_ACCEL_STD", default_value = "0.05")]
+    pub stationary_accel_std: f64,
+
+    /// Angular velocity standard deviation in rad/s below which the body
+    /// may be stationary.
+    #[arg(long, env = "STATIONARY_GYRO_STD", default_value = "0.01")]
+    pub stationary_gyro_std: f64,
+
+    /// Publish short-term strapdown dead-reckoning odometry, reset by zero
+    /// velocity updates while stationary (see --stationary-window).
+    #[arg(long, env = "ODOM")]
+    pub odom: bool,
+
+    /// ros topic for the dead-reckoning odometry.
+    #[arg(long, default_value = "rt/imu/odom")]
+    pub odom_topic: String,
+
+    /// Frame ID of the odometry pose, aligned with the world frame of
+    /// --convention and anchored where the body first came to rest.
+    #[arg(long, env = "ODOM_FRAME", default_value = "odom")]
+    pub odom_frame: String,
+
+    /// Seconds without a zero velocity update after which dead reckoning
+    /// stops publishing until the body comes to rest again.
+    #[arg(long, env = "ODOM_HORIZON", default_value = "10")]
+    pub odom_horizon: f64,
+
+    /// Acceleration noise in m/s²/√Hz driving the growth of the velocity
+    /// and position covariance.
+    #[arg(long, env = "ODOM_ACCEL_NOISE", default_value = "0.1")]
+    pub odom_accel_noise: f64,
+
+    /// Raise latched tilt, tip-over, free-fall and impact alarms on
+    /// --alarm-topic, cleared with a query on <alarm-topic>/clear.
+    #[arg(long, env = "ALARMS")]
+    pub alarms: bool,
+
+    /// ros topic for the alarms. Queries on <topic>/clear clear latched
+    /// alarms and queries on <topic>/events return the recorded events.
+    #[arg(long, default_value = "rt/imu/alarm")]
+    pub alarm_topic: String,
+
+    /// Roll in degrees beyond which the tilt alarm is raised.
+    #[arg(long, env = "ALARM_ROLL", default_value = "30")]
+    pub alarm_roll: f64,
+
+    /// Pitch in degrees beyond which the tilt alarm is raised.
+    #[arg(long, env = "ALARM_PITCH", default_value = "30")]
+    pub alarm_pitch: f64,
+
+    /// Angle in degrees between the body and world vertical beyond which
+    /// the tip-over alarm is raised.
+    #[arg(long, env = "ALARM_TIP_OVER", default_value = "60")]
+    pub alarm_tip_over: f64,
+
+    /// Acceleration in g below which the free-fall alarm is raised after
+    /// 100 ms.
+    #[arg(long, env = "ALARM_FREE_FALL", default_value = "0.3")]
+    pub alarm_free_fall: f64,
+
+    /// Acceleration in g above which the impact alarm is raised.
+    #[arg(long, env = "ALARM_IMPACT", default_value = "4.0")]
+    pub alarm_impact: f64,
+
+    /// Seconds of samples recorded before each alarm.
+    #[arg(long, env = "ALARM_PRE", default_value = "1.0")]
+    pub alarm_pre: f64,
+
+    /// Seconds of samples recorded after each alarm.
+    #[arg(long, env = "ALARM_POST", default_value = "1.0")]
+    pub alarm_post: f64,
+
+    /// Publish the vibration spectrum of the accelerometer axes for
+    /// bearing and motor health monitoring.
+    #[arg(long, env = "VIBRATION")]
+    pub vibration: bool,
+
+    /// ros topic for the vibration spectrum.
+    #[arg(long, default_value = "rt/imu/vibration")]
+    pub vibration_topic: String,
+
+    /// Samples per FFT segment, a power of two. Segments overlap by half.
+    #[arg(long, env = "VIBRATION_WINDOW", default_value = "256", value_parser = vibration::window_size)]
+    pub vibration_window: usize,
+
+    /// Seconds over which segments are averaged before publishing.
+    #[arg(long, env = "VIBRATION_PERIOD", default_value = "1.0")]
+    pub vibration_period: f64,
+
+    /// Frequency bands in Hz as low-high, separated by commas.
+    #[arg(
+        long,
+        env = "VIBRATION_BANDS",
+        default_value = "0-10,10-50,50-100",
+        value_delimiter = ','
+    )]
+    pub vibration_bands: Vec<Band>,
+
+    /// Answer tare queries on <tare-key>/all or <tare-key>/yaw and publish
+    /// the orientation relative to the tare on --relative-topic.
+    #[arg(long, env = "TARE")]
+    pub tare: bool,
+
+    /// Tare with the BNO08x commands, which rotate the on-chip rotation
+    /// vector, or with a host-side reference applied to --relative-topic
+    /// only. The sensor backend is rejected with --fusion-primary and
+    /// --mag-fallback, which publish another orientation.
+    #[arg(long, env = "TARE_BACKEND", value_enum, default_value_t = TareBackend::Sensor)]
+    pub tare_backend: TareBackend,
+
+    /// ros topic for the tared orientation.
+    #[arg(long, default_value = "rt/imu/relative")]
+    pub relative_topic: String,
+
+    /// Key expression prefix of the tare queryable, answering the all,
+    /// yaw, persist and clear commands.
+    #[arg(long, default_value = "rt/imu/tare")]
+    pub tare_key: String,
+
+    /// File the host-side tare reference is persisted to and restored from.
+    #[arg(
+        long,
+        env = "TARE_FILE",
+        default_value = "/var/lib/edgefirst-imu/tare.json"
+    )]
+    pub tare_file: PathBuf,
+
+    /// Publish the discrete vehicle motion state: stationary, moving
+    /// straight, turning left or right, accelerating, braking, on an
+    /// incline or on a bumpy surface.
+    #[arg(long, env = "MOTION")]
+    pub motion: bool,
+
+    /// ros topic for the motion state.
+    #[arg(long, default_value = "rt/imu/motion")]
+    pub motion_topic: String,
+
+    /// Window in seconds over which the motion signals are averaged.
+    #[arg(long, env = "MOTION_WINDOW", default_value = "0.5")]
+    pub motion_window: f64,
+
+    /// Seconds a new motion state must persist before it is reported.
+    #[arg(long, env = "MOTION_HOLD", default_value = "0.3")]
+    pub motion_hold: f64,
+
+    /// Yaw rate in rad/s above which the vehicle is turning.
+    #[arg(long, env = "MOTION_TURN_RATE", default_value = "0.1")]
+    pub motion_turn_rate: f64,
+
+    /// Longitudinal acceleration in m/s² above which the vehicle is
+    /// accelerating or braking.
+    #[arg(long, env = "MOTION_ACCEL", default_value = "0.5")]
+    pub motion_accel: f64,
+
+    /// Pitch in degrees above which the vehicle is on an incline.
+    #[arg(long, env = "MOTION_INCLINE", default_value = "5")]
+    pub motion_incline: f64,
+
+    /// Vertical acceleration standard deviation in m/s² above which the
+    /// surface is bumpy.
+    #[arg(long, env = "MOTION_BUMP_STD", default_value = "1.0")]
+    pub motion_bump_std: f64,
+
+    /// Wheel odometry topic fused with the IMU heading and yaw rate into
+    /// an odometry published on --fused-odom-topic. Empty disables the
+    /// fusion.
+    #[arg(long, env = "WHEEL_TOPIC", default_value = "")]
+    pub wheel_topic: String,
+
+    /// Message type on --wheel-topic.
+    #[arg(long, env = "WHEEL_SOURCE", value_enum, default_value_t = WheelSource::Odometry)]
+    pub wheel_source: WheelSource,
+
+    /// Dimensions of the fused odometry.
+    #[arg(long, env = "WHEEL_MODE", value_enum, default_value_t = WheelMode::Planar)]
+    pub wheel_mode: WheelMode,
+
+    /// Standard deviation of the wheel odometry forward speed in m/s.
+    #[arg(long, env = "WHEEL_SPEED_NOISE", default_value = "0.05")]
+    pub wheel_speed_noise: f64,
+
+    /// Standard deviation of the wheel odometry yaw rate in rad/s.
+    #[arg(long, env = "WHEEL_YAW_RATE_NOISE", default_value = "0.05")]
+    pub wheel_yaw_rate_noise: f64,
+
+    /// ros topic for the wheel and IMU fused odometry.
+    #[arg(long, default_value = "rt/imu/odom/fused")]
+    pub fused_odom_topic: String,
+
+    /// Mounting rotation from the sensor frame to the body frame, as
+    /// quat:x,y,z,w, rpy:roll,pitch,yaw (degrees) or a row-major
+    /// matrix:r00,...,r22. Applied on the host to every sample.
+    #[arg(long, env = "MOUNTING", default_value = "rpy:0,0,0")]
+    pub mounting: Mounting,
+
+    /// Filter chain applied to each linear acceleration axis, as stages
+    /// separated by ';': lowpass:cutoff[,order], notch:frequency[,q] and
+    /// average:n. Empty disables filtering.
+    #[arg(long, env = "ACCEL_FILTER", default_value = "")]
+    pub accel_filter: FilterSpec,
+
+    /// Filter chain applied to each angular velocity axis, in the same
+    /// format as --accel-filter.
+    #[arg(long, env = "GYRO_FILTER", default_value = "")]
+    pub gyro_filter: FilterSpec,
+
+    /// Also publish the unfiltered IMU message on --raw-topic for tuning.
+    #[arg(long, env = "FILTER_RAW")]
+    pub filter_raw: bool,
+
+    /// ros topic for the unfiltered IMU message.
+    #[arg(long, default_value = "rt/imu/raw")]
+    pub raw_topic: String,
+
+    /// Coordinate convention of the published orientation and body vectors.
+    #[arg(long, env = "CONVENTION", value_enum, default_value_t = Convention::Enu)]
+    pub convention: Convention,
+
     /// Application log level
     #[arg(long, env = "RUST_LOG", default_value = "info")]
     pub rust_log: LevelFilter,
@@ -76,6 +622,44 @@ pub struct Args {
     no_multicast_scouting: bool,
 }
 
+/// One-off commands run instead of the service.
+#[derive(Subcommand, Debug, Clone)]
+pub enum Command {
+    /// Records the sensor at rest and computes the Allan deviation of the
+    /// gyroscope and accelerometer.
+    Characterize(CharacterizeArgs),
+    /// Sends a tare command to the running service.
+    Tare(TareArgs),
+    /// Reads every FRS record of the sensor into a JSON file.
+    FrsDump(FrsDumpArgs),
+    /// Writes the FRS records of a JSON file to the sensor.
+    FrsRestore(FrsRestoreArgs),
+}
+
+impl Args {
+    /// Rate in Hz at which samples are processed.
+    pub fn sample_rate(&self) -> f64 {
+        1000.0 / self.sample_interval as f64
+    }
+
+    /// Resolves a topic name against the configured namespace.
+    ///
+    /// Leading and trailing slashes on the namespace and the topic are
+    /// dropped, also without a namespace, as Zenoh keys cannot start or end
+    /// with one: `robot1/`, `/robot1` and `robot1` all produce
+    /// `robot1/rt/imu`, and an empty namespace returns the topic without
+    /// its slashes. A topic starting with a slash is namespaced as well.
+    pub fn resolve_topic(&self, topic: &str) -> String {
+        let namespace = self.namespace.trim_matches('/');
+        let topic = topic.trim_matches('/');
+        if namespace.is_empty() {
+            topic.to_owned()
+        } else {
+            format!("{namespace}/{topic}")
+        }
+    }
+}
+
 impl From<Args> for Config {
     fn from(args: Args) -> Self {
         let mut config = Config::default();
@@ -111,3 +695,45 @@ impl From<Args> for Config {
         config
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(extra: &[&str]) -> Args {
+        Args::parse_from(["edgefirst-imu"].iter().chain(extra))
+    }
+
+    #[test]
+    fn test_resolve_topic() {
+        let args = parse(&[]);
+        assert_eq!(args.resolve_topic("rt/imu"), "rt/imu");
+        assert_eq!(args.resolve_topic("/rt/imu/"), "rt/imu");
+
+        for namespace in ["robot1", "/robot1", "robot1/", "/robot1/"] {
+            let args = parse(&["--namespace", namespace]);
+            assert_eq!(args.resolve_topic("rt/imu"), "robot1/rt/imu", "{namespace}");
+            // Absolute topics are namespaced as well.
+            assert_eq!(
+                args.resolve_topic("/rt/imu"),
+                "robot1/rt/imu",
+                "{namespace}"
+            );
+        }
+
+        let args = parse(&["--namespace", "fleet/robot1"]);
+        assert_eq!(
+            args.resolve_topic("rt/imu/tare"),
+            "fleet/robot1/rt/imu/tare"
+        );
+        // A namespace of only slashes is no namespace.
+        let args = parse(&["--namespace", "/"]);
+        assert_eq!(args.resolve_topic("rt/imu"), "rt/imu");
+    }
+
+    #[test]
+    fn test_frame_id() {
+        assert_eq!(parse(&[]).frame_id, "imu_link");
+        assert_eq!(parse(&["--frame-id", "base_imu"]).frame_id, "base_imu");
+    }
+}
@@ -0,0 +1,121 @@
+// Copyright 2025 Au-Zone Technologies Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Batching of IMU samples into `edgefirst_imu/msg/ImuBatch` messages.
+//!
+//! A batch is flushed once it holds the configured number of samples, or
+//! once the deadline from the first sample of the batch has passed, which
+//! bounds the latency at low rates. The deadline is checked on every sample
+//! and by [`Batcher::poll`], so a partial batch is flushed even when the
+//! samples stop.
+use crate::{args::Args, msgs::ImuBatch, sample::Sample};
+use edgefirst_schemas::std_msgs;
+use std::time::{Duration, Instant};
+
+pub struct Batcher {
+    frame_id: String,
+    size: usize,
+    deadline: Duration,
+    batch: Option<(Instant, ImuBatch)>,
+}
+
+impl Batcher {
+    pub fn new(args: &Args) -> Self {
+        Self {
+            frame_id: args.frame_id.clone(),
+            size: args.batch_size as usize,
+            deadline: Duration::from_millis(args.batch_deadline),
+            batch: None,
+        }
+    }
+
+    /// Adds a sample and returns the batch when it is due.
+    pub fn push(&mut self, sample: &Sample) -> Option<ImuBatch> {
+        let (started, batch) = self.batch.get_or_insert_with(|| {
+            (
+                Instant::now(),
+                ImuBatch {
+                    header: std_msgs::Header {
+                        stamp: sample.stamp.clone(),
+                        frame_id: self.frame_id.clone(),
+                    },
+                    samples: Vec::with_capacity(self.size),
+                },
+            )
+        });
+        batch.samples.push(sample.to_batch_sample());
+
+        if batch.samples.len() >= self.size || started.elapsed() >= self.deadline {
+            self.batch.take().map(|(_, batch)| batch)
+        } else {
+            None
+        }
+    }
+
+    /// Returns the pending batch if its deadline has passed.
+    pub fn poll(&mut self) -> Option<ImuBatch> {
+        if self
+            .batch
+            .as_ref()
+            .is_some_and(|(started, _)| started.elapsed() >= self.deadline)
+        {
+            self.batch.take().map(|(_, batch)| batch)
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::math::{Quat, Vec3};
+    use clap::Parser;
+    use edgefirst_schemas::builtin_interfaces;
+    use std::thread::sleep;
+
+    fn sample(ms: u64) -> Sample {
+        Sample {
+            stamp: builtin_interfaces::Time::from_nanos(ms * 1_000_000),
+            orientation: Quat::new(1.0, 0.0, 0.0, 0.0),
+            angular_velocity: Vec3::default(),
+            linear_acceleration: Vec3::default(),
+            heading_accuracy: 0.0,
+        }
+    }
+
+    #[test]
+    fn test_flushes_full_batch() {
+        let args = Args::parse_from(["edgefirst-imu", "--batch-size", "3"]);
+        let mut batcher = Batcher::new(&args);
+        let flushed: Vec<_> = (0..7).filter_map(|i| batcher.push(&sample(i))).collect();
+        assert_eq!(flushed.len(), 2);
+        for (batch, first) in flushed.iter().zip([0, 3]) {
+            assert_eq!(batch.samples.len(), 3);
+            assert_eq!(batch.header.stamp.to_nanos(), first * 1_000_000);
+        }
+        // The seventh sample waits for the deadline.
+        assert!(batcher.poll().is_none());
+    }
+
+    #[test]
+    fn test_flushes_partial_batch_at_deadline() {
+        let args = Args::parse_from(["edgefirst-imu", "--batch-deadline", "20"]);
+        let mut batcher = Batcher::new(&args);
+        assert!(batcher.poll().is_none());
+        assert!(batcher.push(&sample(0)).is_none());
+        assert!(batcher.push(&sample(5)).is_none());
+        assert!(batcher.poll().is_none());
+
+        // No further samples arrive, the timer flushes the batch.
+        sleep(Duration::from_millis(30));
+        let batch = batcher.poll().unwrap();
+        assert_eq!(batch.samples.len(), 2);
+        assert!(batcher.poll().is_none());
+
+        // A sample arriving late is flushed with its batch on arrival.
+        assert!(batcher.push(&sample(100)).is_none());
+        sleep(Duration::from_millis(30));
+        assert_eq!(batcher.push(&sample(105)).unwrap().samples.len(), 2);
+    }
+}
@@ -0,0 +1,352 @@
+// Copyright 2025 Au-Zone Technologies Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Allan variance characterization of the gyroscope and accelerometer.
+//!
+//! The sensor frame readings are recorded while the sensor is at rest and
+//! the overlapping Allan deviation of each axis is computed over
+//! logarithmically spaced averaging times. The noise terms are read off the
+//! deviation curve: the white noise random walk where the slope is -1/2,
+//! the bias instability at the flat minimum and the rate random walk where
+//! the slope is +1/2. The random walks are noise densities, so their squares
+//! times the sample rate are the per-sample variances the filters expect.
+use crate::{args::Args, driver::Driver, SHUTDOWN};
+use bno08x_rs::{
+    interface::{
+        gpio::{GpiodIn, GpiodOut},
+        spidev::SpiDevice,
+        SpiInterface,
+    },
+    BNO08x, SENSOR_REPORTID_ACCELEROMETER, SENSOR_REPORTID_GYROSCOPE,
+};
+use log::{info, warn};
+use serde_json::{json, Value};
+use std::{
+    fs,
+    path::PathBuf,
+    sync::{atomic::Ordering, Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+/// Averaging times per decade of the deviation curve.
+const POINTS_PER_DECADE: f64 = 10.0;
+
+/// Ratio of the Allan deviation minimum to the bias instability of flicker
+/// noise, sqrt(2 ln 2 / pi).
+const BIAS_INSTABILITY_FACTOR: f64 = 0.664;
+
+/// Largest distance from the ideal slope for a noise term to be reported.
+const SLOPE_TOLERANCE: f64 = 0.25;
+
+/// Interval between progress messages while recording.
+const PROGRESS_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Fewest samples the deviation is computed from.
+const MIN_SAMPLES: usize = 100;
+
+#[derive(clap::Args, Debug, Clone)]
+pub struct CharacterizeArgs {
+    /// Recording time, in seconds or with an s, m or h suffix. Bias
+    /// instability and rate random walk need hours of data.
+    #[arg(long, default_value = "1h", value_parser = parse_duration)]
+    pub duration: Duration,
+
+    /// Path prefix of the results, written to <output>.json and
+    /// <output>.csv.
+    #[arg(long, default_value = "allan")]
+    pub output: PathBuf,
+}
+
+/// Parses a duration in seconds, optionally suffixed with s, m or h.
+pub fn parse_duration(s: &str) -> Result<Duration, String> {
+    let s = s.trim();
+    let (value, scale) = match s.char_indices().last() {
+        Some((i, 'h')) => (&s[..i], 3600.0),
+        Some((i, 'm')) => (&s[..i], 60.0),
+        Some((i, 's')) => (&s[..i], 1.0),
+        _ => (s, 1.0),
+    };
+    let value = value
+        .trim()
+        .parse::<f64>()
+        .map_err(|e| format!("invalid duration {s:?}: {e}"))?;
+    if !value.is_finite() || value <= 0.0 {
+        return Err(format!("duration {s:?} must be positive"));
+    }
+    Ok(Duration::from_secs_f64(value * scale))
+}
+
+/// `(stamp, gyroscope, accelerometer)` in nanoseconds since the start of
+/// the recording and sensor frame units. Single precision halves the
+/// memory of long recordings, about 70 MB for 3 h at 200 Hz.
+type Reading = (u64, [f32; 3], [f32; 3]);
+
+/// Records the sensor at rest for the requested duration and writes the
+/// Allan deviation curves and noise terms.
+pub fn run(args: &Args, characterize: &CharacterizeArgs) -> Result<(), String> {
+    let readings = record(args, characterize.duration)?;
+    if readings.len() < MIN_SAMPLES {
+        return Err(format!(
+            "only {} samples recorded, at least {} are needed",
+            readings.len(),
+            MIN_SAMPLES
+        ));
+    }
+
+    let n = readings.len();
+    let duration = (readings[n - 1].0 - readings[0].0) as f64 * 1e-9;
+    // Measured rather than nominal interval, which the sensor only
+    // approximates.
+    let tau0 = duration / (n - 1) as f64;
+    let ms = averaging_factors(n);
+    let taus: Vec<f64> = ms.iter().map(|m| *m as f64 * tau0).collect();
+    let axis = |index: usize| -> Vec<f64> {
+        let values: Vec<f64> = readings
+            .iter()
+            .map(|(_, gyro, accel)| [gyro, accel][index / 3][index % 3] as f64)
+            .collect();
+        allan_deviation(&values, tau0, &ms)
+    };
+    let deviations: Vec<Vec<f64>> = (0..6).map(axis).collect();
+
+    let mut csv = String::from("tau,gyro_x,gyro_y,gyro_z,accel_x,accel_y,accel_z\n");
+    for (i, tau) in taus.iter().enumerate() {
+        csv.push_str(&tau.to_string());
+        for deviation in &deviations {
+            csv.push_str(&format!(",{}", deviation[i]));
+        }
+        csv.push('\n');
+    }
+
+    let terms = |offset: usize, random_walk: &str| {
+        let mut sensor = serde_json::Map::new();
+        for (i, name) in ["x", "y", "z"].iter().enumerate() {
+            let noise = NoiseTerms::estimate(&taus, &deviations[offset + i]);
+            sensor.insert(
+                name.to_string(),
+                json!({
+                    random_walk: noise.random_walk,
+                    "bias_instability": noise.bias_instability,
+                    "bias_instability_tau": noise.bias_instability_tau,
+                    "rate_random_walk": noise.rate_random_walk,
+                }),
+            );
+        }
+        Value::Object(sensor)
+    };
+    let results = json!({
+        "duration": duration,
+        "samples": n,
+        "sample_rate": 1.0 / tau0,
+        "gyro": terms(0, "angle_random_walk"),
+        "accel": terms(3, "velocity_random_walk"),
+    });
+
+    let json_path = characterize.output.with_extension("json");
+    let csv_path = characterize.output.with_extension("csv");
+    let json = serde_json::to_string_pretty(&results).map_err(|e| e.to_string())?;
+    fs::write(&json_path, json + "\n")
+        .map_err(|e| format!("could not write {}: {}", json_path.display(), e))?;
+    fs::write(&csv_path, csv)
+        .map_err(|e| format!("could not write {}: {}", csv_path.display(), e))?;
+    info!(
+        "Allan deviation written to {} and {}",
+        json_path.display(),
+        csv_path.display()
+    );
+    Ok(())
+}
+
+/// Collects the gyroscope and accelerometer readings until the duration
+/// has elapsed or a shutdown is requested, keeping what was recorded so an
+/// interrupted run still produces results.
+fn record(args: &Args, duration: Duration) -> Result<Vec<Reading>, String> {
+    let mut driver = Driver::new(&args.device, &args.interrupt, &args.reset);
+    driver
+        .imu_driver
+        .init()
+        .map_err(|e| format!("could not initialize driver: {e:?}"))?;
+    let interval = Duration::from_millis(args.sample_interval);
+    driver.enable_report(SENSOR_REPORTID_ACCELEROMETER, interval)?;
+    driver.enable_report(SENSOR_REPORTID_GYROSCOPE, interval)?;
+
+    let expected = (duration.as_secs_f64() / interval.as_secs_f64()) as usize;
+    let readings = Arc::new(Mutex::new(Vec::<Reading>::with_capacity(expected + 1)));
+    let last_reading = Arc::new(Mutex::new(Instant::now()));
+    let start = Instant::now();
+    {
+        let readings = readings.clone();
+        let last_reading = last_reading.clone();
+        driver.imu_driver.add_sensor_report_callback(
+            SENSOR_REPORTID_GYROSCOPE,
+            String::from("characterize_cb"),
+            move |imu_driver: &BNO08x<SpiInterface<SpiDevice, GpiodIn, GpiodOut>>| {
+                let (Ok(gyro), Ok(accel)) = (imu_driver.gyro(), imu_driver.accelerometer()) else {
+                    return;
+                };
+                let now = Instant::now();
+                let stamp = now.duration_since(start).as_nanos() as u64;
+                readings.lock().unwrap().push((stamp, gyro, accel));
+                *last_reading.lock().unwrap() = now;
+            },
+        );
+    }
+
+    info!(
+        "Recording for {:?}, keep the sensor still and at a steady temperature",
+        duration
+    );
+    // Same first message allowance as the publishing loop.
+    let time_limit = Duration::from_millis(args.timeout) * 5;
+    let mut last_progress = start;
+    while start.elapsed() < duration {
+        if SHUTDOWN.load(Ordering::SeqCst) {
+            warn!("Recording interrupted after {:?}", start.elapsed());
+            break;
+        }
+        driver.imu_driver.handle_messages(2, 10);
+
+        let elapsed = last_reading.lock().unwrap().elapsed();
+        if elapsed > time_limit {
+            return Err(format!("no reading from the IMU for {elapsed:?}"));
+        }
+        if last_progress.elapsed() >= PROGRESS_INTERVAL {
+            last_progress = Instant::now();
+            info!(
+                "Recorded {} samples, {:.0}% done",
+                readings.lock().unwrap().len(),
+                100.0 * start.elapsed().as_secs_f64() / duration.as_secs_f64()
+            );
+        }
+    }
+
+    let readings = std::mem::take(&mut *readings.lock().unwrap());
+    Ok(readings)
+}
+
+/// Logarithmically spaced averaging factors up to half the record.
+fn averaging_factors(samples: usize) -> Vec<usize> {
+    let max = (samples - 1) / 2;
+    let mut ms: Vec<usize> = (0..)
+        .map(|i| 10f64.powf(i as f64 / POINTS_PER_DECADE).round() as usize)
+        .take_while(|m| *m <= max)
+        .collect();
+    ms.dedup();
+    ms
+}
+
+/// Overlapping Allan deviation of rate samples `values` taken every `tau0`
+/// seconds, at the averaging times `m * tau0`.
+fn allan_deviation(values: &[f64], tau0: f64, ms: &[usize]) -> Vec<f64> {
+    // Integrated signal, so each cluster average is a difference.
+    let mut theta = Vec::with_capacity(values.len() + 1);
+    theta.push(0.0);
+    for v in values {
+        theta.push(theta[theta.len() - 1] + v * tau0);
+    }
+
+    ms.iter()
+        .map(|&m| {
+            let tau = m as f64 * tau0;
+            let count = theta.len() - 2 * m;
+            let sum: f64 = (0..count)
+                .map(|k| (theta[k + 2 * m] - 2.0 * theta[k + m] + theta[k]).powi(2))
+                .sum();
+            (sum / (2.0 * tau * tau * count as f64)).sqrt()
+        })
+        .collect()
+}
+
+/// Noise terms of one axis in the sensor units.
+#[derive(Debug)]
+struct NoiseTerms {
+    /// Noise density of the white noise, in units/√Hz.
+    random_walk: Option<f64>,
+    /// Bias instability in units.
+    bias_instability: f64,
+    /// Averaging time of the bias instability in seconds.
+    bias_instability_tau: f64,
+    /// Rate random walk in units/s/√Hz.
+    rate_random_walk: Option<f64>,
+}
+
+impl NoiseTerms {
+    fn estimate(taus: &[f64], deviations: &[f64]) -> Self {
+        // Log-log slope at each point from its neighbours.
+        let log = |v: f64| v.max(f64::MIN_POSITIVE).log10();
+        let slopes: Vec<f64> = (0..taus.len())
+            .map(|i| {
+                let (a, b) = (i.saturating_sub(1), (i + 1).min(taus.len() - 1));
+                if a == b {
+                    return 0.0;
+                }
+                (log(deviations[b]) - log(deviations[a])) / (log(taus[b]) - log(taus[a]))
+            })
+            .collect();
+        let closest = |target: f64| {
+            slopes
+                .iter()
+                .enumerate()
+                .map(|(i, s)| (i, (s - target).abs()))
+                .filter(|(_, distance)| *distance <= SLOPE_TOLERANCE)
+                .min_by(|a, b| a.1.total_cmp(&b.1))
+                .map(|(i, _)| i)
+        };
+
+        let (minimum, _) = deviations
+            .iter()
+            .enumerate()
+            .min_by(|a, b| a.1.total_cmp(b.1))
+            .unwrap_or((0, &0.0));
+        Self {
+            // Value of the -1/2 line at tau = 1 s.
+            random_walk: closest(-0.5).map(|i| deviations[i] * taus[i].sqrt()),
+            bias_instability: deviations.get(minimum).copied().unwrap_or(0.0)
+                / BIAS_INSTABILITY_FACTOR,
+            bias_instability_tau: taus.get(minimum).copied().unwrap_or(0.0),
+            // Value of the +1/2 line at tau = 3 s.
+            rate_random_walk: closest(0.5).map(|i| deviations[i] * (3.0 / taus[i]).sqrt()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("3h").unwrap(), Duration::from_secs(10800));
+        assert_eq!(parse_duration("90m").unwrap(), Duration::from_secs(5400));
+        assert_eq!(parse_duration("1.5").unwrap(), Duration::from_millis(1500));
+        assert!(parse_duration("-1s").is_err());
+        assert!(parse_duration("h").is_err());
+    }
+
+    #[test]
+    fn test_white_noise_random_walk() {
+        // Uniform noise from a fixed linear congruential generator, with a
+        // standard deviation of 0.01 at 100 Hz.
+        let mut state = 12345u64;
+        let values: Vec<f64> = (0..100_000)
+            .map(|_| {
+                state = state
+                    .wrapping_mul(6364136223846793005)
+                    .wrapping_add(1442695040888963407);
+                let uniform = (state >> 11) as f64 / (1u64 << 53) as f64;
+                (uniform - 0.5) * 0.01 * 12f64.sqrt()
+            })
+            .collect();
+        let tau0 = 0.01;
+        let ms = averaging_factors(values.len());
+        let taus: Vec<f64> = ms.iter().map(|m| *m as f64 * tau0).collect();
+        let deviations = allan_deviation(&values, tau0, &ms);
+
+        // Single sample clusters have the sample deviation.
+        assert!((deviations[0] - 0.01).abs() < 5e-4);
+        let noise = NoiseTerms::estimate(&taus, &deviations);
+        // Noise density of 0.01 / sqrt(100 Hz).
+        let random_walk = noise.random_walk.unwrap();
+        assert!((random_walk - 0.001).abs() < 1e-4, "{random_walk}");
+    }
+}
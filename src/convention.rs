@@ -0,0 +1,93 @@
+// Copyright 2025 Au-Zone Technologies Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Output coordinate conventions.
+//!
+//! The BNO08x reports orientation against an East-North-Up world frame and
+//! body vectors in a Forward-Left-Up body frame (once the sensor orientation
+//! FRS record matches the mounting), which is the ROS convention from
+//! REP-103 and REP-145. Aerospace consumers such as PX4 expect North-East-Down
+//! and Forward-Right-Down instead.
+use crate::{
+    math::{Quat, Vec3},
+    sample::Sample,
+};
+use clap::ValueEnum;
+use std::f64::consts::FRAC_1_SQRT_2;
+
+/// Rotation taking ENU world coordinates to NED: swap X/Y and negate Z.
+const NED_FROM_ENU: Quat = Quat::new(0.0, FRAC_1_SQRT_2, FRAC_1_SQRT_2, 0.0);
+
+/// Rotation taking FRD body coordinates to FLU: 180 degrees about X.
+const FLU_FROM_FRD: Quat = Quat::new(0.0, 1.0, 0.0, 0.0);
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Convention {
+    /// ENU world frame and FLU body frame (REP-103), as reported by the
+    /// sensor.
+    #[default]
+    Enu,
+    /// NED world frame and FRD body frame for aerospace consumers.
+    Ned,
+}
+
+impl Convention {
+    /// Converts a sample from the sensor's ENU/FLU frames to this
+    /// convention. Orientation, angular velocity and acceleration are
+    /// converted together so the published message stays self-consistent.
+    pub fn apply(&self, sample: &mut Sample) {
+        match self {
+            Self::Enu => {}
+            Self::Ned => {
+                sample.orientation = NED_FROM_ENU * sample.orientation * FLU_FROM_FRD;
+                sample.angular_velocity = flu_to_frd(sample.angular_velocity);
+                sample.linear_acceleration = flu_to_frd(sample.linear_acceleration);
+            }
+        }
+    }
+
+    /// Rotates `q` about the world vertical so its compass heading increases
+    /// by `degrees`, e.g. the declination to go from magnetic to true north.
+    pub fn rotate_heading(&self, q: Quat, degrees: f64) -> Quat {
+        // Heading is clockwise from north, which is a negative rotation
+        // about ENU up and a positive rotation about NED down.
+        let yaw = match self {
+            Self::Enu => -degrees.to_radians(),
+            Self::Ned => degrees.to_radians(),
+        };
+        Quat::from_rpy(0.0, 0.0, yaw) * q
+    }
+}
+
+fn flu_to_frd(v: Vec3) -> Vec3 {
+    Vec3::new(v.x, -v.y, -v.z)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use edgefirst_schemas::builtin_interfaces::Time;
+
+    #[test]
+    fn test_ned_level_east_facing() {
+        // Level and facing east in ENU is a yaw of +90 degrees in NED.
+        let mut sample = Sample {
+            stamp: Time::new(0, 0),
+            orientation: Quat::new(1.0, 0.0, 0.0, 0.0),
+            angular_velocity: Vec3::new(0.1, 0.2, 0.3),
+            linear_acceleration: Vec3::new(0.0, 0.0, 9.81),
+            heading_accuracy: 0.0,
+        };
+        Convention::Ned.apply(&mut sample);
+
+        // q and -q are the same rotation.
+        let q = sample.orientation;
+        let s = q.w.signum();
+        assert!((s * q.w - FRAC_1_SQRT_2).abs() < 1e-12);
+        assert!((s * q.z - FRAC_1_SQRT_2).abs() < 1e-12);
+        assert!(q.x.abs() < 1e-12 && q.y.abs() < 1e-12);
+
+        assert_eq!(sample.angular_velocity, Vec3::new(0.1, -0.2, -0.3));
+        assert_eq!(sample.linear_acceleration, Vec3::new(0.0, 0.0, -9.81));
+    }
+}
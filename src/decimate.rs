@@ -0,0 +1,211 @@
+// Copyright 2025 Au-Zone Technologies Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Output rate decimation.
+//!
+//! The sensor is sampled at the report interval and each output publishes at
+//! its own rate. Rather than dropping the samples in between, the angular
+//! velocity and linear acceleration are averaged over each output period,
+//! which integrates the motion and acts as an anti-aliasing filter. The
+//! orientation is a state rather than a rate, so the latest one is kept.
+use crate::{math::Vec3, sample::Sample};
+use std::str::FromStr;
+
+pub struct Decimator {
+    /// Output period in nanoseconds, zero to pass every sample through.
+    period: u64,
+    start: Option<u64>,
+    angular_velocity: Vec3,
+    linear_acceleration: Vec3,
+    count: u32,
+}
+
+impl Decimator {
+    /// Creates a decimator publishing at `rate` Hz, or at the sample rate
+    /// when `rate` is zero.
+    pub fn new(rate: f64) -> Self {
+        Self {
+            period: if rate > 0.0 {
+                (1e9 / rate).round() as u64
+            } else {
+                0
+            },
+            start: None,
+            angular_velocity: Vec3::default(),
+            linear_acceleration: Vec3::default(),
+            count: 0,
+        }
+    }
+
+    /// Adds a sample and returns the averaged output sample once the
+    /// output period has elapsed. The output carries the stamp and
+    /// orientation of the last sample of the period.
+    pub fn push(&mut self, sample: &Sample) -> Option<Sample> {
+        if self.period == 0 {
+            return Some(sample.clone());
+        }
+
+        let stamp = sample.stamp.to_nanos();
+        let start = *self.start.get_or_insert(stamp);
+        self.angular_velocity = self.angular_velocity + sample.angular_velocity;
+        self.linear_acceleration = self.linear_acceleration + sample.linear_acceleration;
+        self.count += 1;
+
+        if stamp.saturating_sub(start) < self.period {
+            return None;
+        }
+
+        let scale = 1.0 / self.count as f64;
+        let output = Sample {
+            angular_velocity: self.angular_velocity.scale(scale),
+            linear_acceleration: self.linear_acceleration.scale(scale),
+            ..sample.clone()
+        };
+        self.angular_velocity = Vec3::default();
+        self.linear_acceleration = Vec3::default();
+        self.count = 0;
+        // Keep the output on a fixed grid unless it fell more than a period
+        // behind, e.g. after a gap in the reports.
+        let next = start + self.period;
+        self.start = Some(if stamp - next >= self.period {
+            stamp
+        } else {
+            next
+        });
+        Some(output)
+    }
+}
+
+/// Additional output, given as `topic@rate` with the rate in Hz.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OutputSpec {
+    pub topic: String,
+    pub rate: f64,
+}
+
+impl FromStr for OutputSpec {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (topic, rate) = s
+            .rsplit_once('@')
+            .ok_or_else(|| format!("expected topic@rate in {s:?}"))?;
+        let rate = rate
+            .trim()
+            .parse::<f64>()
+            .map_err(|e| format!("invalid rate in {s:?}: {e}"))?;
+        if topic.trim().is_empty() {
+            return Err(format!("missing topic in {s:?}"));
+        }
+        if !rate.is_finite() || rate <= 0.0 {
+            return Err(format!("rate in {s:?} must be positive"));
+        }
+        Ok(Self {
+            topic: topic.trim().to_owned(),
+            rate,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::math::Quat;
+    use edgefirst_schemas::builtin_interfaces;
+
+    /// Sample at `ms` milliseconds with all rates set to `value`.
+    fn sample(ms: u64, value: f64) -> Sample {
+        Sample {
+            stamp: builtin_interfaces::Time::from_nanos(ms * 1_000_000),
+            orientation: Quat::from_rpy(0.0, 0.0, value),
+            angular_velocity: Vec3::new(value, value, value),
+            linear_acceleration: Vec3::new(value, 0.0, 9.81),
+            heading_accuracy: 0.0,
+        }
+    }
+
+    /// Stamps in milliseconds of the outputs for samples at `stamps`.
+    fn outputs(decimator: &mut Decimator, stamps: impl IntoIterator<Item = u64>) -> Vec<u64> {
+        stamps
+            .into_iter()
+            .filter_map(|ms| decimator.push(&sample(ms, 0.0)))
+            .map(|output| output.stamp.to_nanos() / 1_000_000)
+            .collect()
+    }
+
+    #[test]
+    fn test_averages_rates_and_keeps_latest_orientation() {
+        let mut decimator = Decimator::new(50.0);
+        // 200 Hz samples to a 50 Hz output.
+        let outputs: Vec<_> = (0..=8)
+            .filter_map(|i| decimator.push(&sample(i * 5, i as f64)))
+            .collect();
+        assert_eq!(outputs.len(), 2);
+        // The first output covers the samples at 0..=20 ms.
+        let first = &outputs[0];
+        assert_eq!(first.stamp.to_nanos(), 20_000_000);
+        assert!((first.angular_velocity.x - 2.0).abs() < 1e-12);
+        assert!((first.linear_acceleration.x - 2.0).abs() < 1e-12);
+        assert!((first.linear_acceleration.z - 9.81).abs() < 1e-12);
+        assert_eq!(first.orientation, Quat::from_rpy(0.0, 0.0, 4.0));
+        // The second covers 25..=40 ms, with the period starting on the
+        // 20 ms grid point.
+        assert!((outputs[1].angular_velocity.y - 6.5).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_passes_through_at_zero_rate() {
+        let mut decimator = Decimator::new(0.0);
+        assert_eq!(outputs(&mut decimator, [0, 5, 10]), [0, 5, 10]);
+    }
+
+    #[test]
+    fn test_output_grid_alignment() {
+        // Jittered samples do not make the 10 Hz output drift: each output
+        // is the first sample past a 100 ms grid point, rather than 100 ms
+        // after the previous output (104, 209, 314, 419).
+        let mut decimator = Decimator::new(10.0);
+        let stamps = (0..100).map(|i| i * 5 + [0, 1, 4][i as usize % 3]);
+        assert_eq!(outputs(&mut decimator, stamps), [104, 201, 300, 404]);
+    }
+
+    #[test]
+    fn test_gap_restarts_grid() {
+        let mut decimator = Decimator::new(10.0);
+        assert_eq!(outputs(&mut decimator, [0, 50, 100]), [100]);
+        // Reports resume after a 1 s gap: a single output for the gap, then
+        // a grid starting at the first sample after it.
+        assert_eq!(
+            outputs(&mut decimator, [1100, 1150, 1200, 1250, 1300]),
+            [1100, 1200, 1300]
+        );
+        // A stamp going backwards is held until the period has elapsed.
+        assert!(outputs(&mut decimator, [1000]).is_empty());
+    }
+
+    #[test]
+    fn test_output_spec() {
+        assert_eq!(
+            " rt/imu/slow @ 10 ".parse(),
+            Ok(OutputSpec {
+                topic: "rt/imu/slow".to_owned(),
+                rate: 10.0
+            })
+        );
+        // Only the last @ separates the rate.
+        let spec: OutputSpec = "rt/a@b@2.5".parse().unwrap();
+        assert_eq!((spec.topic.as_str(), spec.rate), ("rt/a@b", 2.5));
+        for malformed in [
+            "rt/imu/slow",
+            "rt/imu/slow@",
+            "rt/imu/slow@fast",
+            "@10",
+            "rt/imu/slow@0",
+            "rt/imu/slow@-5",
+            "rt/imu/slow@NaN",
+            "rt/imu/slow@inf",
+        ] {
+            assert!(malformed.parse::<OutputSpec>().is_err(), "{malformed}");
+        }
+    }
+}
@@ -0,0 +1,83 @@
+// Copyright 2025 Au-Zone Technologies Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Magnetic declination used to reference the heading to true north.
+//!
+//! The declination comes from the embedded World Magnetic Model evaluated at
+//! the most recent `sensor_msgs/NavSatFix`, or at the configured position
+//! until a fix is received. Without either the fixed `--declination` value
+//! is used.
+use crate::{
+    args::Args,
+    wmm::{self, MagneticField},
+};
+use edgefirst_schemas::sensor_msgs::{nav_sat_status, NavSatFix};
+use log::{debug, info};
+use std::sync::Mutex;
+
+pub struct Declination {
+    /// Declination in degrees used when no position is known.
+    fallback: f64,
+    /// Decimal year at which the model is evaluated.
+    year: f64,
+    field: Mutex<Option<MagneticField>>,
+}
+
+impl Declination {
+    pub fn new(args: &Args) -> Self {
+        let year = args.magnetic_date.unwrap_or_else(wmm::current_year);
+        let field = match (args.latitude, args.longitude) {
+            (Some(lat), Some(lon)) => {
+                let field = wmm::field(lat, lon, args.altitude, year);
+                info!(
+                    "WMM declination at ({}, {}) for {:.2}: {:.2} deg",
+                    lat, lon, year, field.declination
+                );
+                Some(field)
+            }
+            _ => None,
+        };
+
+        Self {
+            fallback: args.declination,
+            year,
+            field: Mutex::new(field),
+        }
+    }
+
+    /// Updates the model position from a GNSS fix. Fixes without a valid
+    /// position are ignored so the last known declination is kept.
+    pub fn update(&self, fix: &NavSatFix) {
+        if fix.status.status < nav_sat_status::STATUS_FIX
+            || !fix.latitude.is_finite()
+            || !fix.longitude.is_finite()
+        {
+            return;
+        }
+
+        let altitude = if fix.altitude.is_finite() {
+            fix.altitude
+        } else {
+            0.0
+        };
+        let field = wmm::field(fix.latitude, fix.longitude, altitude, self.year);
+        debug!(
+            "WMM declination from NavSatFix: {:.2} deg",
+            field.declination
+        );
+        *self.field.lock().unwrap() = Some(field);
+    }
+
+    /// Model field at the current position, if known.
+    pub fn field(&self) -> Option<MagneticField> {
+        *self.field.lock().unwrap()
+    }
+
+    /// Declination in degrees, east positive.
+    pub fn degrees(&self) -> f64 {
+        self.field
+            .lock()
+            .unwrap()
+            .map_or(self.fallback, |f| f.declination)
+    }
+}
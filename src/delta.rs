@@ -0,0 +1,168 @@
+// Copyright 2025 Au-Zone Technologies Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Delta-angle and delta-velocity integration.
+//!
+//! Each sample contributes an angle increment `Δα = ω dt` and a velocity
+//! increment `Δυ = f dt` over the time since the previous sample. Over an
+//! output interval these are summed with the second order coning and
+//! sculling corrections of Savage ("Strapdown Inertial Navigation
+//! Integration Algorithm Design", JGCD 1998), which account for the body
+//! rotating while the increments are accumulated:
+//!
+//! ```text
+//! φ  = α + ½ Σ (α_{l-1} + Δα_{l-1}/6) × Δα_l
+//! Δv = υ + ½ α × υ
+//!        + ½ Σ [(α_{l-1} + Δα_{l-1}/6) × Δυ_l + (υ_{l-1} + Δυ_{l-1}/6) × Δα_l]
+//! ```
+use crate::{
+    args::Args,
+    math::Vec3,
+    msgs::ImuDelta,
+    sample::{vector3, Sample},
+};
+use edgefirst_schemas::{builtin_interfaces, std_msgs};
+
+/// Parses the output rate, which must be positive and finite.
+pub fn rate(s: &str) -> Result<f64, String> {
+    let rate = s.parse::<f64>().map_err(|e| e.to_string())?;
+    if !rate.is_finite() || rate <= 0.0 {
+        return Err(format!("{s} is not a positive rate"));
+    }
+    Ok(rate)
+}
+
+pub struct DeltaIntegrator {
+    frame_id: String,
+    /// Output interval in nanoseconds.
+    period: u64,
+
+    start: Option<builtin_interfaces::Time>,
+    last: u64,
+    count: u32,
+    /// Summed angle increments.
+    alpha: Vec3,
+    /// Summed velocity increments.
+    upsilon: Vec3,
+    coning: Vec3,
+    sculling: Vec3,
+    last_alpha: Vec3,
+    last_upsilon: Vec3,
+}
+
+impl DeltaIntegrator {
+    pub fn new(args: &Args) -> Self {
+        Self {
+            frame_id: args.frame_id.clone(),
+            period: (1e9 / args.delta_rate).round() as u64,
+            start: None,
+            last: 0,
+            count: 0,
+            alpha: Vec3::default(),
+            upsilon: Vec3::default(),
+            coning: Vec3::default(),
+            sculling: Vec3::default(),
+            last_alpha: Vec3::default(),
+            last_upsilon: Vec3::default(),
+        }
+    }
+
+    /// Integrates a sample and returns the increments once the output
+    /// interval has elapsed.
+    pub fn push(&mut self, sample: &Sample) -> Option<ImuDelta> {
+        let stamp = sample.stamp.to_nanos();
+        let Some(start) = self.start.clone() else {
+            self.start = Some(sample.stamp.clone());
+            self.last = stamp;
+            return None;
+        };
+
+        let dt = stamp.saturating_sub(self.last) as f64 * 1e-9;
+        self.last = stamp;
+        let d_alpha = sample.angular_velocity.scale(dt);
+        let d_upsilon = sample.linear_acceleration.scale(dt);
+
+        let alpha = self.alpha + self.last_alpha.scale(1.0 / 6.0);
+        let upsilon = self.upsilon + self.last_upsilon.scale(1.0 / 6.0);
+        self.coning = self.coning + alpha.cross(&d_alpha).scale(0.5);
+        self.sculling =
+            self.sculling + (alpha.cross(&d_upsilon) + upsilon.cross(&d_alpha)).scale(0.5);
+        self.alpha = self.alpha + d_alpha;
+        self.upsilon = self.upsilon + d_upsilon;
+        self.last_alpha = d_alpha;
+        self.last_upsilon = d_upsilon;
+        self.count += 1;
+
+        if stamp.saturating_sub(start.to_nanos()) < self.period {
+            return None;
+        }
+
+        let delta_angle = self.alpha + self.coning;
+        let delta_velocity =
+            self.upsilon + self.alpha.cross(&self.upsilon).scale(0.5) + self.sculling;
+        let delta = ImuDelta {
+            header: std_msgs::Header {
+                stamp: sample.stamp.clone(),
+                frame_id: self.frame_id.clone(),
+            },
+            start,
+            end: sample.stamp.clone(),
+            delta_angle: vector3(delta_angle),
+            delta_velocity: vector3(delta_velocity),
+            sample_count: self.count,
+        };
+
+        self.start = Some(sample.stamp.clone());
+        self.count = 0;
+        self.alpha = Vec3::default();
+        self.upsilon = Vec3::default();
+        self.coning = Vec3::default();
+        self.sculling = Vec3::default();
+        self.last_alpha = Vec3::default();
+        self.last_upsilon = Vec3::default();
+        Some(delta)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::math::Quat;
+    use clap::Parser;
+
+    #[test]
+    fn test_rotating_specific_force() {
+        // Turning at 1 rad/s about Z with 1 m/s² along body X, integrated
+        // over 0.1 s from 5 ms samples.
+        let args = Args::parse_from(["edgefirst-imu", "--delta", "--delta-rate", "10"]);
+        let mut integrator = DeltaIntegrator::new(&args);
+        let mut delta = None;
+        for i in 0..=20 {
+            let sample = Sample {
+                stamp: builtin_interfaces::Time::from_nanos(1_000_000_000 + i * 5_000_000),
+                orientation: Quat::new(1.0, 0.0, 0.0, 0.0),
+                angular_velocity: Vec3::new(0.0, 0.0, 1.0),
+                linear_acceleration: Vec3::new(1.0, 0.0, 0.0),
+                heading_accuracy: 0.0,
+            };
+            delta = integrator.push(&sample).or(delta);
+        }
+
+        let delta = delta.unwrap();
+        assert_eq!(delta.sample_count, 20);
+        assert!((delta.delta_angle.z - 0.1).abs() < 1e-12);
+        // Exact increment in the start frame is (sin T, 1 - cos T, 0).
+        let v = delta.delta_velocity;
+        assert!((v.x - 0.1f64.sin()).abs() < 2e-4, "{v:?}");
+        assert!((v.y - (1.0 - 0.1f64.cos())).abs() < 1e-5, "{v:?}");
+    }
+
+    #[test]
+    fn test_rejects_invalid_rate() {
+        for rate in ["0", "-10", "NaN", "inf", "fast"] {
+            let args = Args::try_parse_from(["edgefirst-imu", &format!("--delta-rate={rate}")]);
+            assert!(args.is_err(), "{rate}");
+        }
+        assert_eq!(rate("200").unwrap(), 200.0);
+    }
+}
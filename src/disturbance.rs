@@ -0,0 +1,253 @@
+// Copyright 2025 Au-Zone Technologies Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Magnetic disturbance detection.
+//!
+//! The rotation vector heading silently degrades near steel structures and
+//! motors. The monitor compares the measured field strength and dip angle
+//! with the expected values, and checks the sensor's heading accuracy
+//! estimate. The driver does not expose the magnetometer calibration status
+//! bits, so the rotation vector accuracy stands in for them.
+//!
+//! A disturbance is raised as soon as any check fails. It is only cleared
+//! once the field strength and dip have stayed inside half their tolerance,
+//! and the accuracy inside its limit, for the clear time.
+use crate::{
+    args::Args,
+    math::{Quat, Vec3},
+    msgs::MagneticStatus,
+    wmm::MagneticField,
+};
+use edgefirst_schemas::{builtin_interfaces, std_msgs};
+use std::time::{Duration, Instant};
+
+/// Time constant of the learned field strength and dip used when the World
+/// Magnetic Model has no position.
+const BASELINE_TIME_CONSTANT: f64 = 60.0;
+
+/// Fraction of each tolerance the field must return within before the
+/// disturbance can clear.
+const CLEAR_RATIO: f64 = 0.5;
+
+/// Interval at which the status is republished while it does not change.
+const STATUS_INTERVAL: Duration = Duration::from_secs(1);
+
+pub struct MagneticMonitor {
+    frame_id: String,
+    intensity_tolerance: f64,
+    inclination_tolerance: f64,
+    accuracy_limit: f64,
+    clear_time: Duration,
+    fallback: bool,
+
+    /// Learned `(intensity, inclination)` while the field is clean.
+    baseline: Option<(f64, f64)>,
+    last_update: Option<Instant>,
+    disturbed: bool,
+    clean_since: Option<Instant>,
+    last_status: Option<Instant>,
+    /// Rotation aligning the game rotation vector with the rotation vector
+    /// heading, captured while the field is clean.
+    game_alignment: Quat,
+}
+
+/// Result of one monitor update.
+pub struct MagneticCheck {
+    pub status: MagneticStatus,
+    /// Orientation to publish in place of the rotation vector, when the
+    /// game rotation vector fallback is active.
+    pub orientation: Option<Quat>,
+    /// Whether the disturbance state changed on this update.
+    pub changed: bool,
+    /// Whether the status is due to be published, on change or at the
+    /// heartbeat interval.
+    pub publish: bool,
+}
+
+impl MagneticMonitor {
+    pub fn new(args: &Args) -> Self {
+        Self {
+            frame_id: args.frame_id.clone(),
+            intensity_tolerance: args.mag_intensity_tolerance / 100.0,
+            inclination_tolerance: args.mag_inclination_tolerance,
+            accuracy_limit: args.mag_accuracy_limit,
+            clear_time: Duration::from_secs_f64(args.mag_clear_time),
+            fallback: args.mag_fallback,
+            baseline: None,
+            last_update: None,
+            disturbed: false,
+            clean_since: None,
+            last_status: None,
+            game_alignment: Quat::new(1.0, 0.0, 0.0, 0.0),
+        }
+    }
+
+    /// Checks the field for one sample.
+    ///
+    /// `rotation` is the sensor's rotation vector and `game_rotation` its
+    /// game rotation vector, when enabled for the fallback. `field` is the
+    /// calibrated magnetometer reading in
+    /// µT in the same sensor frame, and `accuracy` the rotation vector
+    /// heading accuracy in radians. `expected` is the model field at the
+    /// current position, if known.
+    ///
+    /// Returns `None` until the magnetometer has reported, as the driver
+    /// reads an all-zero field before the first report.
+    pub fn update(
+        &mut self,
+        stamp: &builtin_interfaces::Time,
+        rotation: Quat,
+        game_rotation: Option<Quat>,
+        field: Vec3,
+        accuracy: f64,
+        expected: Option<MagneticField>,
+    ) -> Option<MagneticCheck> {
+        let intensity = field.norm();
+        if !intensity.is_finite() || intensity == 0.0 {
+            return None;
+        }
+        // Likewise the game rotation vector is all zero before its first
+        // report, where valid readings have unit norm.
+        let game_rotation = game_rotation.filter(|q| q.norm().is_finite() && q.norm() > 0.5);
+
+        let now = Instant::now();
+        let dt = self
+            .last_update
+            .map_or(0.0, |t| now.duration_since(t).as_secs_f64());
+        self.last_update = Some(now);
+
+        // Field in the ENU world frame of the rotation vector.
+        let world = rotation.rotate(field);
+        let inclination = (-world.z).atan2(world.x.hypot(world.y)).to_degrees();
+        let accuracy = accuracy.to_degrees();
+
+        let (expected_intensity, expected_inclination) = match expected {
+            // The model is in nT, the magnetometer in µT.
+            Some(f) => (f.intensity / 1000.0, f.inclination),
+            None => *self.baseline.get_or_insert((intensity, inclination)),
+        };
+
+        let intensity_error = (intensity - expected_intensity).abs() / expected_intensity;
+        let inclination_error = (inclination - expected_inclination).abs();
+        let exceeds = |ratio: f64| {
+            intensity_error > self.intensity_tolerance * ratio
+                || inclination_error > self.inclination_tolerance * ratio
+                || accuracy > self.accuracy_limit
+        };
+
+        let was_disturbed = self.disturbed;
+        if exceeds(1.0) {
+            self.disturbed = true;
+            self.clean_since = None;
+        } else if self.disturbed {
+            if exceeds(CLEAR_RATIO) {
+                self.clean_since = None;
+            } else {
+                let since = *self.clean_since.get_or_insert(now);
+                if now.duration_since(since) >= self.clear_time {
+                    self.disturbed = false;
+                    self.clean_since = None;
+                }
+            }
+        }
+
+        if !self.disturbed {
+            if let Some(game) = game_rotation {
+                self.game_alignment = (rotation * game.conjugate()).normalize();
+            }
+            if expected.is_none() {
+                let alpha = (dt / BASELINE_TIME_CONSTANT).min(1.0);
+                if let Some((i, d)) = self.baseline.as_mut() {
+                    *i += alpha * (intensity - *i);
+                    *d += alpha * (inclination - *d);
+                }
+            }
+        }
+
+        let changed = was_disturbed != self.disturbed;
+        let publish = changed
+            || self
+                .last_status
+                .is_none_or(|t| now.duration_since(t) >= STATUS_INTERVAL);
+        if publish {
+            self.last_status = Some(now);
+        }
+
+        let fallback = self.fallback && self.disturbed && game_rotation.is_some();
+        Some(MagneticCheck {
+            status: MagneticStatus {
+                header: std_msgs::Header {
+                    stamp: stamp.clone(),
+                    frame_id: self.frame_id.clone(),
+                },
+                disturbed: self.disturbed,
+                intensity,
+                expected_intensity,
+                inclination,
+                expected_inclination,
+                heading_accuracy: accuracy,
+                game_rotation_fallback: fallback,
+            },
+            orientation: game_rotation
+                .filter(|_| fallback)
+                .map(|game| (self.game_alignment * game).normalize()),
+            changed,
+            publish,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+
+    #[test]
+    fn test_disturbance_hysteresis() {
+        let args = Args::parse_from(["edgefirst-imu", "--mag-clear-time", "0"]);
+        let mut monitor = MagneticMonitor::new(&args);
+        let stamp = builtin_interfaces::Time::new(0, 0);
+        let identity = Quat::new(1.0, 0.0, 0.0, 0.0);
+        // 50 µT pointing north and dipping 60 degrees.
+        let (s, c) = 60f64.to_radians().sin_cos();
+        let clean = Vec3::new(0.0, 50.0 * c, -50.0 * s);
+        let mut check = |field: Vec3| {
+            monitor
+                .update(&stamp, identity, None, field, 0.0, None)
+                .unwrap()
+                .status
+                .disturbed
+        };
+
+        assert!(!check(clean));
+        // Field strength 30% above the learned baseline.
+        assert!(check(clean.scale(1.3)));
+        // Within tolerance but outside half of it: still disturbed.
+        assert!(check(clean.scale(1.1)));
+        assert!(!check(clean));
+    }
+
+    #[test]
+    fn test_ignores_field_before_first_report() {
+        let args = Args::parse_from(["edgefirst-imu", "--mag-fallback"]);
+        let mut monitor = MagneticMonitor::new(&args);
+        let stamp = builtin_interfaces::Time::new(0, 0);
+        let identity = Quat::new(1.0, 0.0, 0.0, 0.0);
+        let zero = Quat::new(0.0, 0.0, 0.0, 0.0);
+        assert!(monitor
+            .update(&stamp, identity, Some(zero), Vec3::default(), 0.0, None)
+            .is_none());
+
+        // The baseline is learned from the first real field instead.
+        let field = Vec3::new(0.0, 25.0, -43.0);
+        for _ in 0..3 {
+            let check = monitor
+                .update(&stamp, identity, Some(zero), field, 0.0, None)
+                .unwrap();
+            assert!(!check.status.disturbed);
+            assert!(check.status.expected_intensity.is_finite());
+            assert!(check.orientation.is_none());
+        }
+        assert_eq!(monitor.game_alignment, identity);
+    }
+}
@@ -19,9 +19,7 @@ pub struct Driver<'a> {
     pub imu_driver: BNO08x<'a, SpiInterface<SpiDevice, GpiodIn, GpiodOut>>,
 }
 
-pub const ROTATION_VECTOR_UPDATE: Duration = Duration::from_millis(5);
-pub const ACCELEROMETER_UPDATE: Duration = Duration::from_millis(20);
-pub const GYROSCOPE_UPDATE: Duration = Duration::from_millis(20);
+pub const MAGNETOMETER_UPDATE: Duration = Duration::from_millis(20);
 
 impl Driver<'_> {
     /// Creates a Driver struct object initializing the driver wrapper
@@ -35,42 +33,49 @@ impl Driver<'_> {
         Self { imu_driver }
     }
 
-    /// Settings to set for the driver that was initialized.
-    pub fn enable_reports(&mut self) -> Result<(), String> {
+    /// Enables the rotation vector, accelerometer and gyroscope reports at
+    /// the given intervals. The sensor only accepts whole milliseconds.
+    pub fn enable_reports(
+        &mut self,
+        rotation_vector: Duration,
+        accelerometer: Duration,
+        gyroscope: Duration,
+    ) -> Result<(), String> {
         let reports = [
-            (
-                SENSOR_REPORTID_ROTATION_VECTOR,
-                ROTATION_VECTOR_UPDATE.as_millis() as u16,
-            ),
-            (
-                SENSOR_REPORTID_ACCELEROMETER,
-                ACCELEROMETER_UPDATE.as_millis() as u16,
-            ),
-            (
-                SENSOR_REPORTID_GYROSCOPE,
-                GYROSCOPE_UPDATE.as_millis() as u16,
-            ),
+            (SENSOR_REPORTID_ROTATION_VECTOR, rotation_vector),
+            (SENSOR_REPORTID_ACCELEROMETER, accelerometer),
+            (SENSOR_REPORTID_GYROSCOPE, gyroscope),
         ];
 
-        let max_tries = 5;
-
         for (r, t) in reports {
-            let mut i = 0;
-            while i < max_tries && !self.imu_driver.is_report_enabled(r) {
-                let _ = self.imu_driver.enable_report(r, t);
-                i += 1;
-            }
+            self.enable_report(r, t)?;
+        }
+        Ok(())
+    }
 
-            if !self.imu_driver.is_report_enabled(r) {
-                return Err(format!("Could not enable report {}", r));
-            }
+    /// Enables a single report at the given interval, retrying as the
+    /// sensor does not always acknowledge the first request.
+    pub fn enable_report(&mut self, report_id: u8, interval: Duration) -> Result<(), String> {
+        let max_tries = 5;
+
+        let mut i = 0;
+        while i < max_tries && !self.imu_driver.is_report_enabled(report_id) {
+            let _ = self
+                .imu_driver
+                .enable_report(report_id, interval.as_millis() as u16);
+            i += 1;
+        }
 
-            delay_ms(100);
+        if !self.imu_driver.is_report_enabled(report_id) {
+            return Err(format!("Could not enable report {}", report_id));
         }
+
+        delay_ms(100);
         Ok(())
     }
 
-    pub fn configure_frs(&mut self) -> Result<(), String> {
+    /// Prepares an initialized sensor for FRS requests.
+    pub fn enable_frs(&mut self) -> Result<(), String> {
         // Need to enable a report so that the IMU reports back to the program.
         // Writes don't seem to work if the IMU doesn't also have anything send
         let max_tries = 5;
@@ -88,23 +93,6 @@ impl Driver<'_> {
             ));
         }
         delay_ms(1000);
-
-        let mut last_err = "Success".to_string();
-        for _ in 0..max_tries {
-            // The driver will normalize the quaternion so we don't need to normalize it
-            // ourselves
-            match self
-                .imu_driver
-                .set_sensor_orientation(-1.0, 0.0, 0.0, 1.0, 2000)
-            {
-                Ok(v) if v => return Ok(()),
-                Ok(_) => last_err = "FRS records write failed".to_string(),
-                Err(e) => last_err = format!("{:?}", e),
-            }
-        }
-        Err(format!(
-            "Did not update sensor orientation FRS records after {} tries. The last error was {}",
-            max_tries, last_err,
-        ))
+        Ok(())
     }
 }
@@ -0,0 +1,148 @@
+// Copyright 2025 Au-Zone Technologies Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Euler angles and compass heading derived from the published orientation.
+use crate::{args::Args, convention::Convention, math::Quat, msgs::Euler, sample::Sample};
+use clap::ValueEnum;
+use edgefirst_schemas::std_msgs;
+
+/// Below this value of `cos(second angle)` the conversion is treated as
+/// gimbal locked and the third angle is folded into the first.
+const GIMBAL_LOCK_THRESHOLD: f64 = 1e-6;
+
+/// Intrinsic Tait-Bryan rotation order used to decompose the orientation.
+/// `zyx` is yaw, then pitch, then roll, matching ROS roll/pitch/yaw.
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum EulerOrder {
+    #[default]
+    Zyx,
+    Zxy,
+    Yxz,
+    Yzx,
+    Xyz,
+    Xzy,
+}
+
+impl EulerOrder {
+    fn axes(&self) -> [usize; 3] {
+        match self {
+            Self::Zyx => [2, 1, 0],
+            Self::Zxy => [2, 0, 1],
+            Self::Yxz => [1, 0, 2],
+            Self::Yzx => [1, 2, 0],
+            Self::Xyz => [0, 1, 2],
+            Self::Xzy => [0, 2, 1],
+        }
+    }
+
+    /// Decomposes `q` into rotations about X, Y and Z in radians, returned
+    /// as `[roll, pitch, yaw]` regardless of the order they are applied in.
+    ///
+    /// At gimbal lock the third rotation is set to zero and the combined
+    /// rotation is reported on the first axis, so the result stays finite
+    /// and continuous in the remaining degree of freedom.
+    pub fn decompose(&self, q: &Quat) -> [f64; 3] {
+        let r = q.rotation_matrix();
+        let [i, j, k] = self.axes();
+        // +1 for cyclic orders (xyz, yzx, zxy), -1 otherwise.
+        let parity = if (j + 3 - i) % 3 == 1 { 1.0 } else { -1.0 };
+
+        let sin_b = (parity * r[i][k]).clamp(-1.0, 1.0);
+        let b = sin_b.asin();
+        let (a, c) = if b.cos() > GIMBAL_LOCK_THRESHOLD {
+            (
+                (-parity * r[j][k]).atan2(r[k][k]),
+                (-parity * r[i][j]).atan2(r[i][i]),
+            )
+        } else {
+            ((parity * r[k][j]).atan2(r[j][j]), 0.0)
+        };
+
+        let mut angles = [0.0; 3];
+        angles[i] = a;
+        angles[j] = b;
+        angles[k] = c;
+        angles
+    }
+}
+
+/// Compass heading of the body forward axis in degrees clockwise from the
+/// reference north, in `[0, 360)`.
+pub fn heading(q: &Quat, convention: Convention) -> f64 {
+    let [_, _, yaw] = EulerOrder::Zyx.decompose(q);
+    let heading = match convention {
+        // ENU yaw is counter-clockwise from east.
+        Convention::Enu => 90.0 - yaw.to_degrees(),
+        // NED yaw is clockwise from north.
+        Convention::Ned => yaw.to_degrees(),
+    };
+    heading.rem_euclid(360.0)
+}
+
+/// Builds the Euler angle message for `sample`. Angles are in degrees and
+/// `declination` (east positive) converts between magnetic and true
+/// heading, depending on which north the orientation is referenced to.
+pub fn euler_message(sample: &Sample, args: &Args, declination: f64) -> Euler {
+    let order = args.euler_order;
+    let [roll, pitch, yaw] = order.decompose(&sample.orientation);
+    let heading = heading(&sample.orientation, args.convention);
+    let (magnetic_heading, true_heading) = if args.true_north {
+        ((heading - declination).rem_euclid(360.0), heading)
+    } else {
+        (heading, (heading + declination).rem_euclid(360.0))
+    };
+    Euler {
+        header: std_msgs::Header {
+            stamp: sample.stamp.clone(),
+            frame_id: args.frame_id.clone(),
+        },
+        roll: roll.to_degrees(),
+        pitch: pitch.to_degrees(),
+        yaw: yaw.to_degrees(),
+        magnetic_heading,
+        true_heading,
+        declination,
+        accuracy: sample.heading_accuracy.to_degrees(),
+        order: order
+            .to_possible_value()
+            .map(|v| v.get_name().to_owned())
+            .unwrap_or_default(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(a: [f64; 3], b: [f64; 3]) {
+        for (a, b) in a.iter().zip(b) {
+            assert!((a - b).abs() < 1e-9, "{a} != {b}");
+        }
+    }
+
+    #[test]
+    fn test_zyx_round_trip() {
+        let (roll, pitch, yaw) = (0.3, -0.4, 2.5);
+        let q = Quat::from_rpy(roll, pitch, yaw);
+        assert_close(EulerOrder::Zyx.decompose(&q), [roll, pitch, yaw]);
+    }
+
+    #[test]
+    fn test_gimbal_lock_is_finite() {
+        let q = Quat::from_rpy(0.2, std::f64::consts::FRAC_PI_2, 0.5);
+        let [roll, pitch, yaw] = EulerOrder::Zyx.decompose(&q);
+        assert!((pitch - std::f64::consts::FRAC_PI_2).abs() < 1e-6);
+        assert_eq!(roll, 0.0);
+        // Only yaw - roll is observable at +90 degrees of pitch.
+        assert!((yaw - 0.3).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_heading() {
+        // Facing east in ENU and in NED.
+        let east = Quat::from_rpy(0.0, 0.0, 0.0);
+        assert!((heading(&east, Convention::Enu) - 90.0).abs() < 1e-9);
+        let east = Quat::from_rpy(0.0, 0.0, std::f64::consts::FRAC_PI_2);
+        assert!((heading(&east, Convention::Ned) - 90.0).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,346 @@
+// Copyright 2025 Au-Zone Technologies Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Digital filters applied to the linear acceleration and angular velocity
+//! before publishing.
+//!
+//! Each channel has its own chain of stages, applied in order to every axis:
+//!
+//! - `lowpass:cutoff[,order]` Butterworth low-pass, cutoff in Hz, even order
+//!   (default 2) built from cascaded biquads
+//! - `notch:frequency[,q]` notch at the frequency in Hz with quality factor
+//!   `q` (default 5), for vibration at a known frequency
+//! - `average:n` moving average over the last `n` samples
+//!
+//! Stages are separated by `;`, e.g. `lowpass:20,4;notch:47`.
+use crate::{args::Args, math::Vec3, sample::Sample};
+use std::{collections::VecDeque, f64::consts::PI, str::FromStr};
+
+const DEFAULT_ORDER: usize = 2;
+const DEFAULT_Q: f64 = 5.0;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Stage {
+    Lowpass { cutoff: f64, order: usize },
+    Notch { frequency: f64, q: f64 },
+    Average { length: usize },
+}
+
+/// Parsed filter chain specification for one channel.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct FilterSpec {
+    stages: Vec<Stage>,
+}
+
+impl FromStr for FilterSpec {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let stages = s
+            .split(';')
+            .map(str::trim)
+            .filter(|stage| !stage.is_empty())
+            .map(parse_stage)
+            .collect::<Result<_, _>>()?;
+        Ok(Self { stages })
+    }
+}
+
+fn parse_stage(s: &str) -> Result<Stage, String> {
+    let (kind, values) = s
+        .split_once(':')
+        .ok_or_else(|| format!("expected lowpass:, notch: or average: prefix in {s:?}"))?;
+    let values = values
+        .split(',')
+        .map(|v| v.trim().parse::<f64>())
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("invalid number in {s:?}: {e}"))?;
+
+    let stage = match (kind.trim(), values.as_slice()) {
+        ("lowpass", &[cutoff]) => Stage::Lowpass {
+            cutoff,
+            order: DEFAULT_ORDER,
+        },
+        ("lowpass", &[cutoff, order]) => {
+            if order < 2.0 || order % 2.0 != 0.0 {
+                return Err(format!("lowpass order {order} must be even"));
+            }
+            Stage::Lowpass {
+                cutoff,
+                order: order as usize,
+            }
+        }
+        ("notch", &[frequency]) => Stage::Notch {
+            frequency,
+            q: DEFAULT_Q,
+        },
+        ("notch", &[frequency, q]) => Stage::Notch { frequency, q },
+        ("average", &[length]) => {
+            if length < 1.0 || length.fract() != 0.0 {
+                return Err(format!(
+                    "average length {length} must be a positive integer"
+                ));
+            }
+            Stage::Average {
+                length: length as usize,
+            }
+        }
+        ("lowpass", _) => return Err("lowpass: expects cutoff[,order]".to_owned()),
+        ("notch", _) => return Err("notch: expects frequency[,q]".to_owned()),
+        ("average", _) => return Err("average: expects a sample count".to_owned()),
+        (kind, _) => return Err(format!("unknown filter {kind:?}")),
+    };
+
+    match stage {
+        Stage::Lowpass { cutoff: f, .. } | Stage::Notch { frequency: f, .. } if f <= 0.0 => {
+            Err(format!("frequency in {s:?} must be positive"))
+        }
+        Stage::Notch { q, .. } if q <= 0.0 => Err(format!("q in {s:?} must be positive")),
+        stage => Ok(stage),
+    }
+}
+
+/// Second order section in transposed direct form II.
+#[derive(Debug, Clone)]
+struct Biquad {
+    b: [f64; 3],
+    a: [f64; 2],
+    state: [f64; 2],
+}
+
+impl Biquad {
+    /// Builds a section from RBJ cookbook coefficients, normalized by `a0`.
+    fn new(b: [f64; 3], a: [f64; 3]) -> Self {
+        Self {
+            b: b.map(|v| v / a[0]),
+            a: [a[1] / a[0], a[2] / a[0]],
+            state: [0.0; 2],
+        }
+    }
+
+    fn lowpass(cutoff: f64, q: f64, rate: f64) -> Self {
+        let (sin, cos) = (2.0 * PI * cutoff / rate).sin_cos();
+        let alpha = sin / (2.0 * q);
+        let b = (1.0 - cos) / 2.0;
+        Self::new([b, 2.0 * b, b], [1.0 + alpha, -2.0 * cos, 1.0 - alpha])
+    }
+
+    fn notch(frequency: f64, q: f64, rate: f64) -> Self {
+        let (sin, cos) = (2.0 * PI * frequency / rate).sin_cos();
+        let alpha = sin / (2.0 * q);
+        Self::new(
+            [1.0, -2.0 * cos, 1.0],
+            [1.0 + alpha, -2.0 * cos, 1.0 - alpha],
+        )
+    }
+
+    fn process(&mut self, x: f64) -> f64 {
+        let y = self.b[0] * x + self.state[0];
+        self.state[0] = self.b[1] * x - self.a[0] * y + self.state[1];
+        self.state[1] = self.b[2] * x - self.a[1] * y;
+        y
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Section {
+    Biquad(Biquad),
+    Average {
+        length: usize,
+        window: VecDeque<f64>,
+        sum: f64,
+    },
+}
+
+impl Section {
+    fn process(&mut self, x: f64) -> f64 {
+        match self {
+            Self::Biquad(biquad) => biquad.process(x),
+            Self::Average {
+                length,
+                window,
+                sum,
+            } => {
+                window.push_back(x);
+                *sum += x;
+                if window.len() > *length {
+                    *sum -= window.pop_front().unwrap_or_default();
+                }
+                *sum / window.len() as f64
+            }
+        }
+    }
+}
+
+/// Filter chain for one scalar signal.
+#[derive(Debug, Clone)]
+struct Chain {
+    sections: Vec<Section>,
+    /// The first sample primes the filter state so the output does not
+    /// start from zero.
+    primed: bool,
+}
+
+impl Chain {
+    fn new(spec: &FilterSpec, rate: f64) -> Result<Self, String> {
+        let nyquist = rate / 2.0;
+        let mut sections = Vec::new();
+        for stage in &spec.stages {
+            match *stage {
+                Stage::Lowpass { cutoff, order } => {
+                    if cutoff >= nyquist {
+                        return Err(format!(
+                            "lowpass cutoff {cutoff} Hz must be below {nyquist} Hz"
+                        ));
+                    }
+                    // Butterworth pole pairs of an order n filter.
+                    for k in 0..order / 2 {
+                        let angle = (2 * k + 1) as f64 * PI / (2 * order) as f64;
+                        let q = 1.0 / (2.0 * angle.cos());
+                        sections.push(Section::Biquad(Biquad::lowpass(cutoff, q, rate)));
+                    }
+                }
+                Stage::Notch { frequency, q } => {
+                    if frequency >= nyquist {
+                        return Err(format!(
+                            "notch frequency {frequency} Hz must be below {nyquist} Hz"
+                        ));
+                    }
+                    sections.push(Section::Biquad(Biquad::notch(frequency, q, rate)));
+                }
+                Stage::Average { length } => sections.push(Section::Average {
+                    length,
+                    window: VecDeque::with_capacity(length + 1),
+                    sum: 0.0,
+                }),
+            }
+        }
+        Ok(Self {
+            sections,
+            primed: false,
+        })
+    }
+
+    fn process(&mut self, x: f64) -> f64 {
+        if !self.primed {
+            self.primed = true;
+            self.prime(x);
+        }
+        self.sections.iter_mut().fold(x, |x, s| s.process(x))
+    }
+
+    /// Sets each section to its steady state for a constant input `x`. All
+    /// stages have unit gain at DC, so the input passes through unchanged.
+    fn prime(&mut self, x: f64) {
+        for section in &mut self.sections {
+            match section {
+                Section::Biquad(biquad) => {
+                    let [b0, b1, b2] = biquad.b;
+                    let [a1, a2] = biquad.a;
+                    let y = x * (b0 + b1 + b2) / (1.0 + a1 + a2);
+                    biquad.state = [y - b0 * x, b2 * x - a2 * y];
+                }
+                Section::Average { .. } => {}
+            }
+        }
+    }
+}
+
+/// Filter chains for the acceleration and angular velocity axes.
+#[derive(Debug, Clone)]
+pub struct Filters {
+    accel: Option<[Chain; 3]>,
+    gyro: Option<[Chain; 3]>,
+}
+
+impl Filters {
+    /// Builds the chains for samples arriving at the sample rate, checking
+    /// every frequency against the Nyquist limit.
+    ///
+    /// The filters run once per sample, so a report slower than the samples
+    /// would be filtered as a staircase of held values. Such a filter is
+    /// rejected rather than designed for a rate it does not run at.
+    pub fn new(args: &Args) -> Result<Self, String> {
+        let rate = args.sample_rate();
+        let axes =
+            |name: &str, spec: &FilterSpec, interval: u64| -> Result<Option<[Chain; 3]>, String> {
+                if spec.stages.is_empty() {
+                    return Ok(None);
+                }
+                if interval != args.sample_interval {
+                    return Err(format!(
+                        "--{name}-filter needs --{name}-interval equal to --sample-interval \
+                     ({interval} ms, expected {} ms)",
+                        args.sample_interval
+                    ));
+                }
+                let chain = Chain::new(spec, rate)?;
+                Ok(Some([chain.clone(), chain.clone(), chain]))
+            };
+        Ok(Self {
+            accel: axes("accel", &args.accel_filter, args.accel_interval)?,
+            gyro: axes("gyro", &args.gyro_filter, args.gyro_interval)?,
+        })
+    }
+
+    pub fn apply(&mut self, sample: &mut Sample) {
+        if let Some(chains) = &mut self.accel {
+            sample.linear_acceleration = filter(chains, sample.linear_acceleration);
+        }
+        if let Some(chains) = &mut self.gyro {
+            sample.angular_velocity = filter(chains, sample.angular_velocity);
+        }
+    }
+}
+
+fn filter(chains: &mut [Chain; 3], v: Vec3) -> Vec3 {
+    Vec3::new(
+        chains[0].process(v.x),
+        chains[1].process(v.y),
+        chains[2].process(v.z),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+
+    /// Output amplitude of a unit sine at `frequency` after settling,
+    /// from the RMS over a whole number of cycles.
+    fn amplitude(spec: &str, frequency: f64) -> f64 {
+        let rate = 200.0;
+        let mut chain = Chain::new(&spec.parse().unwrap(), rate).unwrap();
+        let power = (0..4000)
+            .map(|i| chain.process((2.0 * PI * frequency * i as f64 / rate).sin()))
+            .skip(2000)
+            .map(|y| y * y)
+            .sum::<f64>()
+            / 2000.0;
+        (2.0 * power).sqrt()
+    }
+
+    #[test]
+    fn test_lowpass_and_notch_response() {
+        // Butterworth response is -3 dB at the cutoff.
+        let cutoff = amplitude("lowpass:20,4", 20.0);
+        assert!((cutoff - 0.5f64.sqrt()).abs() < 0.01, "{cutoff}");
+        assert!(amplitude("lowpass:20,4", 2.0) > 0.99);
+        assert!(amplitude("lowpass:20,4", 80.0) < 0.01);
+        assert!(amplitude("notch:47,5", 47.0) < 0.01);
+        assert!(amplitude("notch:47,5", 10.0) > 0.95);
+    }
+
+    #[test]
+    fn test_rejects_invalid_specs() {
+        assert!("lowpass:20,3".parse::<FilterSpec>().is_err());
+        assert!("highpass:20".parse::<FilterSpec>().is_err());
+        let filters =
+            |extra: &[&str]| Filters::new(&Args::parse_from(["edgefirst-imu"].iter().chain(extra)));
+        let accel = ["--accel-interval", "5", "--accel-filter"];
+        assert!(filters(&[&accel[..], &["notch:120"]].concat()).is_err());
+        assert!(filters(&[&accel[..], &["notch:47"]].concat()).is_ok());
+        // Held reports at a slower rate than the samples.
+        assert!(filters(&["--gyro-interval", "20", "--gyro-filter", "lowpass:20"]).is_err());
+    }
+}
@@ -0,0 +1,394 @@
+// Copyright 2025 Au-Zone Technologies Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Dumps the FRS records of the sensor to a JSON file and restores them.
+//!
+//! The records are read and written as 32-bit words with the raw SH-2 FRS
+//! requests of `sh2.rs`. The file is keyed by record name, with the
+//! calibration config and sensor metadata records grouped; an empty record
+//! is `null`. A restore only writes the records present in the file, erases
+//! the `null` ones and checks each write by reading the record back. The
+//! sensor metadata is read-only and the serial number is only restored on
+//! request, so a dump can be cloned onto other units.
+use crate::{args::Args, driver::Driver, math::Quat, mounting::SensorOrientation, sh2::Sh2};
+use log::{info, warn};
+use serde_json::{Map, Value};
+use std::{fs, path::PathBuf};
+
+/// FRS record of the sensor orientation, Q30 x, y, z and w words.
+pub const SYSTEM_ORIENTATION: u16 = 0x2D3E;
+
+/// Scale of the Q30 fixed point words.
+const Q30: f64 = (1u32 << 30) as f64;
+
+#[derive(clap::Args, Debug, Clone)]
+pub struct FrsDumpArgs {
+    /// JSON file the records are written to.
+    pub file: PathBuf,
+}
+
+#[derive(clap::Args, Debug, Clone)]
+pub struct FrsRestoreArgs {
+    /// JSON file holding the records.
+    pub file: PathBuf,
+
+    /// Also restore the serial number record, which is otherwise skipped so
+    /// units keep their own.
+    #[arg(long)]
+    pub include_serial: bool,
+}
+
+/// Content of a record, `None` when it is empty.
+type Words = Option<Vec<u32>>;
+
+/// How a record is restored.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Restore {
+    Write,
+    /// Only with --include-serial.
+    Serial,
+    ReadOnly,
+}
+
+struct Record {
+    /// Object of the file holding the record, if grouped.
+    group: Option<&'static str>,
+    name: &'static str,
+    frs_type: u16,
+    restore: Restore,
+}
+
+impl Record {
+    const fn new(
+        group: Option<&'static str>,
+        name: &'static str,
+        frs_type: u16,
+        restore: Restore,
+    ) -> Self {
+        Self {
+            group,
+            name,
+            frs_type,
+            restore,
+        }
+    }
+
+    /// Name of the record in the logs.
+    fn path(&self) -> String {
+        match self.group {
+            Some(group) => format!("{}.{}", group, self.name),
+            None => self.name.to_string(),
+        }
+    }
+
+    /// Shows the content of the record.
+    fn describe(&self, words: Option<&[u32]>) -> String {
+        match words {
+            _ if self.frs_type == SYSTEM_ORIENTATION => describe_orientation(words),
+            None => "empty".to_string(),
+            Some(words) => format!("{} words", words.len()),
+        }
+    }
+}
+
+const CALIBRATION: Option<&str> = Some("calibration_config");
+const METADATA: Option<&str> = Some("sensor_metadata");
+
+/// Records dumped and restored, in order.
+const RECORDS: &[Record] = &[
+    Record::new(
+        None,
+        "system_orientation",
+        SYSTEM_ORIENTATION,
+        Restore::Write,
+    ),
+    Record::new(CALIBRATION, "static_agm", 0x7979, Restore::Write),
+    Record::new(CALIBRATION, "nominal_agm", 0x4D4D, Restore::Write),
+    Record::new(CALIBRATION, "static_sra", 0x8A8A, Restore::Write),
+    Record::new(CALIBRATION, "nominal_sra", 0x4E4E, Restore::Write),
+    Record::new(METADATA, "raw_accelerometer", 0xE301, Restore::ReadOnly),
+    Record::new(METADATA, "accelerometer", 0xE302, Restore::ReadOnly),
+    Record::new(METADATA, "linear_acceleration", 0xE303, Restore::ReadOnly),
+    Record::new(METADATA, "gravity", 0xE304, Restore::ReadOnly),
+    Record::new(METADATA, "raw_gyroscope", 0xE305, Restore::ReadOnly),
+    Record::new(METADATA, "gyroscope", 0xE306, Restore::ReadOnly),
+    Record::new(
+        METADATA,
+        "gyroscope_uncalibrated",
+        0xE307,
+        Restore::ReadOnly,
+    ),
+    Record::new(METADATA, "raw_magnetometer", 0xE308, Restore::ReadOnly),
+    Record::new(METADATA, "magnetic_field", 0xE309, Restore::ReadOnly),
+    Record::new(
+        METADATA,
+        "magnetic_field_uncalibrated",
+        0xE30A,
+        Restore::ReadOnly,
+    ),
+    Record::new(METADATA, "rotation_vector", 0xE30B, Restore::ReadOnly),
+    Record::new(METADATA, "game_rotation_vector", 0xE30C, Restore::ReadOnly),
+    Record::new(
+        METADATA,
+        "geomagnetic_rotation_vector",
+        0xE30D,
+        Restore::ReadOnly,
+    ),
+    Record::new(None, "serial_number", 0x4B4B, Restore::Serial),
+    Record::new(None, "dynamic_calibration", 0x1F1F, Restore::Write),
+];
+
+/// Encodes an orientation as the words of the system orientation record.
+fn orientation_words(q: &Quat) -> Vec<u32> {
+    [q.x, q.y, q.z, q.w]
+        .map(|v| (v * Q30).round() as i32 as u32)
+        .to_vec()
+}
+
+/// Shows the orientation held by the words of a system orientation record.
+fn describe_orientation(words: Option<&[u32]>) -> String {
+    match words {
+        None => "empty".to_string(),
+        Some(&[x, y, z, w]) => {
+            let [x, y, z, w] = [x, y, z, w].map(|v| v as i32 as f64 / Q30);
+            format!("x: {x:.6} y: {y:.6} z: {z:.6} w: {w:.6}")
+        }
+        Some(words) => format!("invalid, {} words", words.len()),
+    }
+}
+
+/// Initializes the sensor and takes over its interface for FRS requests.
+pub fn open(args: &Args) -> Result<Sh2, String> {
+    let mut driver = Driver::new(&args.device, &args.interrupt, &args.reset);
+    driver
+        .imu_driver
+        .init()
+        .map_err(|e| format!("could not initialize driver: {e:?}"))?;
+    driver.enable_frs()?;
+    Ok(Sh2::new(driver))
+}
+
+/// Shows the current and requested content of a record, then writes the
+/// requested one unless `dry_run` and checks it by reading it back. `None`
+/// erases the record.
+fn update(sh2: &mut Sh2, record: &Record, requested: Words, dry_run: bool) -> Result<(), String> {
+    let path = record.path();
+    let current = sh2.read_frs(record.frs_type)?;
+    info!("{} current: {}", path, record.describe(current.as_deref()));
+    info!(
+        "{} requested: {}",
+        path,
+        record.describe(requested.as_deref())
+    );
+    if current == requested {
+        info!("{} unchanged", path);
+        return Ok(());
+    }
+    if dry_run {
+        info!("Dry run, {} not updated", path);
+        return Ok(());
+    }
+
+    sh2.write_frs(record.frs_type, requested.as_deref().unwrap_or_default())?;
+    let written = sh2.read_frs(record.frs_type)?;
+    if written != requested {
+        return Err(format!(
+            "{} reads back as {}",
+            path,
+            record.describe(written.as_deref())
+        ));
+    }
+    info!("{} written and verified", path);
+    Ok(())
+}
+
+/// Writes the system orientation record, or only shows it with `dry_run`.
+pub fn configure_orientation(sh2: &mut Sh2, q: &Quat, dry_run: bool) -> Result<(), String> {
+    update(sh2, &RECORDS[0], Some(orientation_words(q)), dry_run)
+}
+
+/// Reads every record into the JSON object of a dump. A record the sensor
+/// cannot read is left out, so a restore does not erase it.
+fn read_records(sh2: &mut Sh2) -> Map<String, Value> {
+    let mut records = Map::new();
+    for record in RECORDS {
+        let words = match sh2.read_frs(record.frs_type) {
+            Ok(words) => words,
+            Err(e) => {
+                warn!("Skipping {}: {}", record.path(), e);
+                continue;
+            }
+        };
+        info!("{}: {}", record.path(), record.describe(words.as_deref()));
+        let value = serde_json::to_value(words).unwrap();
+        let object = match record.group {
+            Some(group) => records
+                .entry(group)
+                .or_insert_with(|| Value::Object(Map::new()))
+                .as_object_mut()
+                .unwrap(),
+            None => &mut records,
+        };
+        object.insert(record.name.to_string(), value);
+    }
+    records
+}
+
+/// Writes every record of the sensor to the file.
+pub fn dump(args: &Args, frs: &FrsDumpArgs) -> Result<(), String> {
+    let mut sh2 = open(args)?;
+    let records = read_records(&mut sh2);
+    let json = serde_json::to_string_pretty(&records).unwrap();
+    fs::write(&frs.file, json + "\n")
+        .map_err(|e| format!("could not write {}: {}", frs.file.display(), e))?;
+    info!("FRS records written to {}", frs.file.display());
+    Ok(())
+}
+
+/// Finds a record by group and name.
+fn find(group: Option<&str>, name: &str) -> Option<(usize, &'static Record)> {
+    RECORDS
+        .iter()
+        .enumerate()
+        .find(|(_, record)| record.group == group && record.name == name)
+}
+
+/// Parses the content of a record: words, `null` for an empty record or,
+/// for the system orientation, any --configure-orientation value.
+fn parse_words(record: &Record, value: &Value) -> Result<Words, String> {
+    let words = match value {
+        Value::String(s) if record.frs_type == SYSTEM_ORIENTATION => s
+            .parse::<SensorOrientation>()
+            .map(|orientation| Some(orientation_words(&orientation.rotation.normalize()))),
+        _ => serde_json::from_value::<Words>(value.clone()).map_err(|e| e.to_string()),
+    }
+    .map_err(|e| format!("invalid {}: {}", record.path(), e))?;
+    // A record written with no words is erased.
+    Ok(words.filter(|words| !words.is_empty()))
+}
+
+/// Parses a records file into the records it holds, in restore order.
+fn parse(json: &str) -> Result<Vec<(&'static Record, Words)>, String> {
+    let Value::Object(file) = serde_json::from_str(json).map_err(|e| e.to_string())? else {
+        return Err("the records are not a JSON object".to_string());
+    };
+    let mut records = Vec::new();
+    for (key, value) in &file {
+        if RECORDS.iter().any(|record| record.group == Some(key)) {
+            let Value::Object(group) = value else {
+                return Err(format!("{} is not an object", key));
+            };
+            for (name, value) in group {
+                let (i, record) =
+                    find(Some(key), name).ok_or_else(|| format!("unknown record {key}.{name}"))?;
+                records.push((i, record, parse_words(record, value)?));
+            }
+        } else {
+            let (i, record) = find(None, key).ok_or_else(|| format!("unknown record {key}"))?;
+            records.push((i, record, parse_words(record, value)?));
+        }
+    }
+    records.sort_by_key(|(i, _, _)| *i);
+    Ok(records
+        .into_iter()
+        .map(|(_, record, words)| (record, words))
+        .collect())
+}
+
+/// Writes the records of the file, or only shows them with --dry-run.
+pub fn restore(args: &Args, frs: &FrsRestoreArgs) -> Result<(), String> {
+    let json = fs::read_to_string(&frs.file)
+        .map_err(|e| format!("could not read {}: {}", frs.file.display(), e))?;
+    let records = parse(&json)?;
+    let mut sh2 = open(args)?;
+    for (record, words) in records {
+        match record.restore {
+            Restore::ReadOnly => {
+                info!("Skipping {}, the record is read-only", record.path());
+                continue;
+            }
+            Restore::Serial if !frs.include_serial => {
+                info!("Skipping {}, restored with --include-serial", record.path());
+                continue;
+            }
+            _ => {}
+        }
+        update(&mut sh2, record, words, args.dry_run)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_records() {
+        let records = parse(
+            r#"{"dynamic_calibration": [0, 1], "serial_number": null, "system_orientation": "maivin2",
+                "calibration_config": {"nominal_sra": []}}"#,
+        )
+        .unwrap();
+        let records: Vec<_> = records
+            .iter()
+            .map(|(record, words)| (record.path(), words.clone()))
+            .collect();
+        assert_eq!(
+            records,
+            [
+                (
+                    "system_orientation".to_string(),
+                    Some(vec![0xD2BEC333, 0, 0, 0x2D413CCD])
+                ),
+                ("calibration_config.nominal_sra".to_string(), None),
+                ("serial_number".to_string(), None),
+                ("dynamic_calibration".to_string(), Some(vec![0, 1])),
+            ]
+        );
+        assert!(parse(r#"{"system_orientation": "quat:0,0,0,2"}"#).is_err());
+        assert!(parse(r#"{"orientation": "identity"}"#).is_err());
+        assert!(parse(r#"{"serial_number": "1234"}"#).is_err());
+        assert!(parse(r#"{"sensor_metadata": {"compass": null}}"#).is_err());
+        assert!(parse(r#"{"sensor_metadata": [1]}"#).is_err());
+        assert!(parse(r#"[1]"#).is_err());
+    }
+
+    #[test]
+    fn test_dump_round_trip() {
+        let mut dump = Map::new();
+        for (i, record) in RECORDS.iter().enumerate() {
+            let words = (i % 3 != 0).then(|| vec![i as u32; i]);
+            let value = serde_json::to_value(words).unwrap();
+            match record.group {
+                Some(group) => dump
+                    .entry(group)
+                    .or_insert_with(|| Value::Object(Map::new()))
+                    .as_object_mut()
+                    .unwrap()
+                    .insert(record.name.to_string(), value),
+                None => dump.insert(record.name.to_string(), value),
+            };
+        }
+        let records = parse(&serde_json::to_string(&dump).unwrap()).unwrap();
+        assert_eq!(records.len(), RECORDS.len());
+        for (i, (record, words)) in records.iter().enumerate() {
+            assert_eq!(record.frs_type, RECORDS[i].frs_type);
+            assert_eq!(*words, (i % 3 != 0).then(|| vec![i as u32; i]));
+        }
+    }
+
+    #[test]
+    fn test_orientation_words() {
+        let q: SensorOrientation = "maivin2".parse().unwrap();
+        let words = orientation_words(&q.rotation);
+        assert_eq!(words, [0xD2BEC333, 0, 0, 0x2D413CCD]);
+        assert_eq!(
+            describe_orientation(Some(&words)),
+            "x: -0.707107 y: 0.000000 z: 0.000000 w: 0.707107"
+        );
+        let identity = orientation_words(&Quat::new(1.0, 0.0, 0.0, 0.0));
+        assert_eq!(identity, [0, 0, 0, 1 << 30]);
+        assert_eq!(describe_orientation(None), "empty");
+        assert_eq!(describe_orientation(Some(&[0, 1])), "invalid, 2 words");
+    }
+}
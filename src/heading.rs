@@ -0,0 +1,244 @@
+// Copyright 2025 Au-Zone Technologies Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+//! External heading aiding.
+//!
+//! Headings from another source, such as a dual-antenna GNSS receiver,
+//! GNSS course over ground or visual odometry, are blended into the
+//! published orientation. A scalar Kalman filter tracks the offset between
+//! the IMU heading and the external heading: the offset is assumed to drift
+//! slowly as a random walk and each external heading is a noisy measurement
+//! of it. The published orientation is rotated about the vertical by the
+//! estimated offset, so the gyroscope still provides the short term yaw.
+use crate::{args::Args, convention::Convention, euler, math::Quat, sample::Sample};
+use clap::ValueEnum;
+use edgefirst_schemas::{builtin_interfaces, geometry_msgs, sensor_msgs, serde_cdr};
+use log::{debug, info, warn};
+use std::{collections::VecDeque, time::Instant};
+
+/// Length of the IMU heading history used to match external headings to
+/// the IMU heading at their timestamp, in nanoseconds.
+const HISTORY: u64 = 2_000_000_000;
+
+/// Message carrying the external heading.
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum HeadingSource {
+    /// `geometry_msgs/TwistStamped` with the ENU velocity; the course over
+    /// ground is used while moving faster than --heading-min-speed.
+    #[default]
+    Velocity,
+    /// `sensor_msgs/Imu` whose ENU orientation carries the heading, as
+    /// published by dual-antenna GNSS receivers.
+    Imu,
+    /// `geometry_msgs/TransformStamped` whose ENU rotation carries the
+    /// heading, such as a visual odometry pose.
+    Transform,
+}
+
+impl HeadingSource {
+    /// Decodes a message into its timestamp and compass heading in degrees
+    /// clockwise from north. Returns `None` when the message carries no
+    /// usable heading, such as a velocity below `min_speed`.
+    pub fn decode(
+        &self,
+        payload: &[u8],
+        min_speed: f64,
+    ) -> Result<Option<(builtin_interfaces::Time, f64)>, String> {
+        let err = |e: serde_cdr::Error| e.to_string();
+        let (stamp, q) = match self {
+            Self::Velocity => {
+                let msg: geometry_msgs::TwistStamped =
+                    serde_cdr::deserialize(payload).map_err(err)?;
+                let v = msg.twist.linear;
+                if v.x.hypot(v.y) < min_speed {
+                    return Ok(None);
+                }
+                // Course over ground, clockwise from north.
+                let heading = v.x.atan2(v.y).to_degrees().rem_euclid(360.0);
+                return Ok(Some((msg.header.stamp, heading)));
+            }
+            Self::Imu => {
+                let msg: sensor_msgs::IMU = serde_cdr::deserialize(payload).map_err(err)?;
+                (msg.header.stamp, msg.orientation)
+            }
+            Self::Transform => {
+                let msg: geometry_msgs::TransformStamped =
+                    serde_cdr::deserialize(payload).map_err(err)?;
+                (msg.header.stamp, msg.transform.rotation)
+            }
+        };
+        let q = Quat::new(q.w, q.x, q.y, q.z);
+        if !q.norm().is_finite() || q.norm() < 0.5 {
+            return Ok(None);
+        }
+        Ok(Some((
+            stamp,
+            euler::heading(&q.normalize(), Convention::Enu),
+        )))
+    }
+}
+
+pub struct HeadingAid {
+    /// Measurement noise of the external heading in degrees.
+    noise: f64,
+    /// Random walk of the offset in degrees per square root second.
+    drift: f64,
+    timeout: f64,
+    /// Largest stamp difference of an IMU heading match in nanoseconds.
+    max_skew: u64,
+    convention: Convention,
+
+    /// Heading correction in degrees added to the IMU heading.
+    offset: f64,
+    /// Variance of the offset in square degrees, `None` until the first
+    /// external heading or after a timeout.
+    variance: Option<f64>,
+    last_fix: Option<Instant>,
+    /// Recent `(stamp, heading)` pairs of the unaided IMU heading.
+    history: VecDeque<(u64, f64)>,
+}
+
+impl HeadingAid {
+    pub fn new(args: &Args) -> Self {
+        Self {
+            noise: args.heading_noise,
+            drift: args.heading_drift,
+            timeout: args.heading_timeout,
+            max_skew: (args.heading_max_skew.max(0.0) * 1e9) as u64,
+            convention: args.convention,
+            offset: 0.0,
+            variance: None,
+            last_fix: None,
+            history: VecDeque::new(),
+        }
+    }
+
+    /// Records the IMU heading of an output frame sample and applies the
+    /// current heading correction to it.
+    pub fn apply(&mut self, sample: &mut Sample) {
+        let stamp = sample.stamp.to_nanos();
+        let heading = euler::heading(&sample.orientation, self.convention);
+        self.history.push_back((stamp, heading));
+        while self
+            .history
+            .front()
+            .is_some_and(|(t, _)| stamp.saturating_sub(*t) > HISTORY)
+        {
+            self.history.pop_front();
+        }
+
+        if self.variance.is_some()
+            && self
+                .last_fix
+                .is_some_and(|t| t.elapsed().as_secs_f64() > self.timeout)
+        {
+            warn!(
+                "No external heading for {} s, holding the last correction",
+                self.timeout
+            );
+            self.variance = None;
+        }
+
+        sample.orientation = self
+            .convention
+            .rotate_heading(sample.orientation, self.offset);
+    }
+
+    /// Blends an external compass heading in degrees, measured at `stamp`,
+    /// into the heading correction.
+    pub fn measure(&mut self, stamp: &builtin_interfaces::Time, heading: f64) {
+        let Some(imu) = self.imu_heading(stamp.to_nanos()) else {
+            debug!("No IMU heading within the skew of the external heading");
+            return;
+        };
+        let now = Instant::now();
+        let measured = wrap(heading - imu);
+
+        self.offset = match self.variance {
+            None => {
+                info!(
+                    "External heading aiding active, correction {:.1} deg",
+                    measured
+                );
+                self.variance = Some(self.noise * self.noise);
+                measured
+            }
+            Some(variance) => {
+                let dt = self
+                    .last_fix
+                    .map_or(0.0, |t| now.duration_since(t).as_secs_f64());
+                let predicted = variance + self.drift * self.drift * dt;
+                let gain = predicted / (predicted + self.noise * self.noise);
+                self.variance = Some((1.0 - gain) * predicted);
+                wrap(self.offset + gain * wrap(measured - self.offset))
+            }
+        };
+        self.last_fix = Some(now);
+    }
+
+    /// Unaided IMU heading closest in time to `stamp`, if within the
+    /// maximum skew.
+    fn imu_heading(&self, stamp: u64) -> Option<f64> {
+        self.history
+            .iter()
+            .min_by_key(|(t, _)| t.abs_diff(stamp))
+            .filter(|(t, _)| t.abs_diff(stamp) <= self.max_skew)
+            .map(|(_, heading)| *heading)
+    }
+}
+
+/// Wraps an angle in degrees to `[-180, 180)`.
+fn wrap(degrees: f64) -> f64 {
+    (degrees + 180.0).rem_euclid(360.0) - 180.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{euler::EulerOrder, math::Vec3};
+    use clap::Parser;
+
+    #[test]
+    fn test_blends_across_north() {
+        let args = Args::parse_from(["edgefirst-imu", "--heading-topic", "rt/gnss"]);
+        let mut aid = HeadingAid::new(&args);
+        // IMU says 355 degrees (yaw +95 degrees in ENU).
+        let imu = Quat::from_rpy(0.0, 0.0, 95f64.to_radians());
+        let mut sample = Sample {
+            stamp: builtin_interfaces::Time::new(10, 0),
+            orientation: imu,
+            angular_velocity: Vec3::default(),
+            linear_acceleration: Vec3::default(),
+            heading_accuracy: 0.0,
+        };
+        aid.apply(&mut sample);
+        // External heading of 5 degrees, ten degrees clockwise of the IMU.
+        aid.measure(&sample.stamp, 5.0);
+        for _ in 0..10 {
+            aid.measure(&sample.stamp, 5.0);
+        }
+        sample.orientation = imu;
+        aid.apply(&mut sample);
+        let yaw = EulerOrder::Zyx.decompose(&sample.orientation)[2].to_degrees();
+        assert!((yaw - 85.0).abs() < 1e-6, "{yaw}");
+    }
+
+    #[test]
+    fn test_ignores_unmatched_stamps() {
+        let args = Args::parse_from(["edgefirst-imu", "--heading-topic", "rt/gnss"]);
+        let mut aid = HeadingAid::new(&args);
+        let mut sample = Sample {
+            stamp: builtin_interfaces::Time::new(10, 0),
+            orientation: Quat::from_rpy(0.0, 0.0, 0.0),
+            angular_velocity: Vec3::default(),
+            linear_acceleration: Vec3::default(),
+            heading_accuracy: 0.0,
+        };
+        aid.apply(&mut sample);
+        // One second away from the only IMU heading.
+        aid.measure(&builtin_interfaces::Time::new(11, 0), 45.0);
+        assert!(aid.variance.is_none());
+        aid.measure(&builtin_interfaces::Time::new(10, 50_000_000), 45.0);
+        assert!(aid.variance.is_some());
+    }
+}
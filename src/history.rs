@@ -0,0 +1,147 @@
+// Copyright 2025 Au-Zone Technologies Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Recent angular velocity history for ego-rotation queries.
+//!
+//! Motion compensation of radar scans and rolling-shutter frames needs the
+//! sensor rotation over the scan or exposure, which generally does not line
+//! up with the IMU samples. The history keeps the angular velocity of the
+//! last few seconds and integrates it over any requested interval, each
+//! sample holding its value over the time since the previous sample and
+//! samples straddling an interval boundary being split at it.
+use crate::{
+    args::Args,
+    math::{Quat, Vec3},
+    msgs::EgoRotation,
+    sample::{quaternion, vector3, Sample},
+};
+use edgefirst_schemas::{builtin_interfaces, std_msgs};
+use std::collections::VecDeque;
+
+/// Gap between samples in nanoseconds over which the angular velocity is
+/// unknown, such as across a sensor reset.
+const MAX_GAP: u64 = 500_000_000;
+
+pub struct RotationHistory {
+    frame_id: String,
+    /// History length in nanoseconds.
+    length: u64,
+    /// `(stamp, angular velocity)` in time order.
+    samples: VecDeque<(u64, Vec3)>,
+}
+
+impl RotationHistory {
+    pub fn new(args: &Args) -> Self {
+        Self {
+            frame_id: args.frame_id.clone(),
+            length: (args.rotation_history * 1e9) as u64,
+            samples: VecDeque::new(),
+        }
+    }
+
+    pub fn push(&mut self, sample: &Sample) {
+        let stamp = sample.stamp.to_nanos();
+        if self.samples.back().is_some_and(|(t, _)| *t >= stamp) {
+            return;
+        }
+        self.samples.push_back((stamp, sample.angular_velocity));
+        while self
+            .samples
+            .get(1)
+            .is_some_and(|(t, _)| stamp - t > self.length)
+        {
+            self.samples.pop_front();
+        }
+    }
+
+    /// Integrates the rotation from `start` to `end`, both in nanoseconds
+    /// since the epoch. Fails unless the history covers the whole interval.
+    pub fn rotation(&self, start: u64, end: u64) -> Result<EgoRotation, String> {
+        if end <= start {
+            return Err(format!("end {end} must be after start {start}"));
+        }
+        match (self.samples.front(), self.samples.back()) {
+            (Some((first, _)), Some((last, _))) if *first <= start && end <= *last => {}
+            (Some((first, _)), Some((last, _))) => {
+                return Err(format!(
+                    "interval {start}..{end} is outside the history {first}..{last}"
+                ))
+            }
+            _ => return Err("no IMU samples yet".to_owned()),
+        }
+
+        let mut rotation = Quat::new(1.0, 0.0, 0.0, 0.0);
+        let mut angle = Vec3::default();
+        let mut count = 0;
+        let pairs = self.samples.iter().zip(self.samples.iter().skip(1));
+        for (&(previous, _), &(stamp, gyro)) in pairs {
+            let (lo, hi) = (previous.max(start), stamp.min(end));
+            if hi <= lo {
+                continue;
+            }
+            if stamp - previous > MAX_GAP {
+                return Err(format!("gap in the IMU history at {previous}"));
+            }
+            let phi = gyro.scale((hi - lo) as f64 * 1e-9);
+            rotation = (rotation * Quat::from_rotation_vector(phi)).normalize();
+            angle = angle + phi;
+            count += 1;
+        }
+
+        Ok(EgoRotation {
+            header: std_msgs::Header {
+                stamp: builtin_interfaces::Time::from_nanos(end),
+                frame_id: self.frame_id.clone(),
+            },
+            start: builtin_interfaces::Time::from_nanos(start),
+            end: builtin_interfaces::Time::from_nanos(end),
+            rotation: quaternion(rotation),
+            angular_velocity: vector3(angle.scale(1e9 / (end - start) as f64)),
+            sample_count: count,
+        })
+    }
+}
+
+/// Parses the `start` and `end` query parameters, in nanoseconds since the
+/// epoch.
+pub fn parse_interval(start: Option<&str>, end: Option<&str>) -> Result<(u64, u64), String> {
+    let parse = |name: &str, value: Option<&str>| {
+        value
+            .ok_or_else(|| format!("missing {name} parameter"))?
+            .parse::<u64>()
+            .map_err(|e| format!("invalid {name} parameter: {e}"))
+    };
+    Ok((parse("start", start)?, parse("end", end)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+
+    #[test]
+    fn test_integrates_partial_interval() {
+        let args = Args::parse_from(["edgefirst-imu", "--rotation-query"]);
+        let mut history = RotationHistory::new(&args);
+        let rate = Vec3::new(0.0, 0.0, 2.0);
+        for i in 0..100u64 {
+            history.push(&Sample {
+                stamp: builtin_interfaces::Time::from_nanos(1_000_000_000 + i * 5_000_000),
+                orientation: Quat::new(1.0, 0.0, 0.0, 0.0),
+                angular_velocity: rate,
+                linear_acceleration: Vec3::default(),
+                heading_accuracy: 0.0,
+            });
+        }
+
+        // 33 ms starting between samples.
+        let msg = history.rotation(1_101_000_000, 1_134_000_000).unwrap();
+        let yaw = 2.0 * msg.rotation.z.atan2(msg.rotation.w);
+        assert!((yaw - 0.066).abs() < 1e-9, "{yaw}");
+        assert!((msg.angular_velocity.z - 2.0).abs() < 1e-9);
+        assert_eq!(msg.sample_count, 7);
+
+        assert!(history.rotation(1_400_000_000, 1_500_000_000).is_err());
+        assert!(history.rotation(1_134_000_000, 1_101_000_000).is_err());
+    }
+}
@@ -0,0 +1,6 @@
+// Copyright 2025 Au-Zone Technologies Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Message definitions of the EdgeFirst IMU service, for consumers decoding
+//! its custom topics such as the batched IMU messages.
+pub mod msgs;
@@ -1,22 +1,73 @@
 // Copyright 2025 Au-Zone Technologies Inc.
 // SPDX-License-Identifier: Apache-2.0
 
+mod ahrs;
+mod alarm;
 mod args;
+mod batch;
+mod characterize;
+mod convention;
+mod decimate;
+mod declination;
+mod delta;
+mod disturbance;
 mod driver;
-
-use args::Args;
+mod euler;
+mod filter;
+mod frs;
+mod heading;
+mod history;
+mod math;
+mod motion;
+mod mounting;
+mod odometry;
+mod preintegration;
+mod sample;
+mod sh2;
+mod stationary;
+mod tare;
+mod vibration;
+mod wheel;
+mod wmm;
+
+use ahrs::{Ahrs, FusionAlgorithm};
+use alarm::AlarmMonitor;
+use args::{Args, Command};
+use batch::Batcher;
 use bno08x_rs::{
     interface::{
         gpio::{GpiodIn, GpiodOut},
         spidev::SpiDevice,
         SpiInterface,
     },
-    BNO08x, SENSOR_REPORTID_ROTATION_VECTOR,
+    BNO08x, SENSOR_REPORTID_ACCELEROMETER, SENSOR_REPORTID_GYROSCOPE,
+    SENSOR_REPORTID_MAGNETIC_FIELD, SENSOR_REPORTID_ROTATION_VECTOR,
+    SENSOR_REPORTID_ROTATION_VECTOR_GAME,
 };
 use clap::Parser;
+use decimate::Decimator;
+use declination::Declination;
+use delta::DeltaIntegrator;
+use disturbance::MagneticMonitor;
 use driver::Driver;
-use edgefirst_schemas::{builtin_interfaces, geometry_msgs, sensor_msgs, serde_cdr, std_msgs};
+use edgefirst_imu::msgs::{self, TFMessage};
+use edgefirst_schemas::{
+    builtin_interfaces, geometry_msgs,
+    sensor_msgs::{self, NavSatFix},
+    serde_cdr, std_msgs,
+};
+use filter::Filters;
+use heading::HeadingAid;
+use history::RotationHistory;
 use log::{debug, error, info, trace, warn};
+use math::{Quat, Vec3};
+use motion::MotionClassifier;
+use odometry::DeadReckoning;
+use preintegration::Preintegrator;
+use sample::Sample;
+use serde::Serialize;
+use sh2::Sh2;
+use stationary::StationaryDetector;
 use std::{
     sync::{
         atomic::{AtomicBool, Ordering},
@@ -24,6 +75,7 @@ use std::{
     },
     time::{Duration, Instant, SystemTime, SystemTimeError, UNIX_EPOCH},
 };
+use tare::Tare;
 
 /// Global shutdown flag for graceful termination.
 /// This is critical for coverage instrumentation - LLVM uses atexit() handlers
@@ -76,8 +128,11 @@ fn install_signal_handlers() {
 use tracing::info_span;
 use tracing_subscriber::{layer::SubscriberExt as _, Layer as _, Registry};
 use tracy_client::frame_mark;
+use vibration::VibrationAnalyzer;
+use wheel::WheelOdometry;
 use zenoh::{
     bytes::{Encoding, ZBytes},
+    query::Query,
     Session, Wait,
 };
 
@@ -88,16 +143,6 @@ fn main() {
     install_signal_handlers();
 
     let args = Args::parse();
-    if args.configure {
-        let mut driver = Driver::new(&args.device, &args.interrupt, &args.reset);
-        driver.imu_driver.init().unwrap();
-        match driver.configure_frs() {
-            Ok(_) => info!("FRS records updated"),
-            Err(e) => error!("ERROR: FRS records not updated: {}", e),
-        }
-        return;
-    }
-
     args.tracy.then(tracy_client::Client::start);
 
     let stdout_log = tracing_subscriber::fmt::layer()
@@ -121,11 +166,182 @@ fn main() {
     tracing::subscriber::set_global_default(subscriber).expect("setting default subscriber failed");
     tracing_log::LogTracer::init().unwrap();
 
+    // After the logging setup so the record is shown.
+    if args.configure {
+        configure(&args);
+        return;
+    }
+
+    match &args.command {
+        Some(Command::Characterize(characterize)) => {
+            if let Err(e) = characterize::run(&args, characterize) {
+                error!("Characterization failed: {}", e);
+            }
+            return;
+        }
+        Some(Command::Tare(command)) => {
+            match tare::request(&args, command.action) {
+                Ok(state) => info!(
+                    "Tare mode {} offset w: {} x: {} y: {} z: {} persisted: {}",
+                    state.mode,
+                    state.offset.w,
+                    state.offset.x,
+                    state.offset.y,
+                    state.offset.z,
+                    state.persisted
+                ),
+                Err(e) => error!("Tare failed: {}", e),
+            }
+            return;
+        }
+        Some(Command::FrsDump(frs)) => {
+            if let Err(e) = frs::dump(&args, frs) {
+                error!("ERROR: FRS records not dumped: {}", e);
+                std::process::exit(1);
+            }
+            return;
+        }
+        Some(Command::FrsRestore(frs)) => {
+            if let Err(e) = frs::restore(&args, frs) {
+                error!("ERROR: FRS records not restored: {}", e);
+                std::process::exit(1);
+            }
+            return;
+        }
+        None => {}
+    }
+
+    let filters = match Filters::new(&args) {
+        Ok(filters) => filters,
+        Err(e) => {
+            error!("Invalid filter configuration: {}", e);
+            return;
+        }
+    };
+    if let Err(e) = vibration::check_rates(&args) {
+        error!("Invalid vibration configuration: {}", e);
+        return;
+    }
+    if let Err(e) = tare::check_backend(&args) {
+        error!("Invalid tare configuration: {}", e);
+        return;
+    }
+
     let session = zenoh::open(args.clone()).wait().unwrap();
 
+    let declination = Arc::new(Declination::new(&args));
+    let _navsat_subscriber = if args.navsat_topic.is_empty() {
+        None
+    } else {
+        let declination = declination.clone();
+        let topic = args.resolve_topic(&args.navsat_topic);
+        info!("Updating magnetic declination from {}", topic);
+        let subscriber = session
+            .declare_subscriber(topic)
+            .callback(move |sample| {
+                match serde_cdr::deserialize::<NavSatFix>(&sample.payload().to_bytes()) {
+                    Ok(fix) => declination.update(&fix),
+                    Err(e) => warn!("Failed to decode NavSatFix: {}", e),
+                }
+            })
+            .wait()
+            .unwrap();
+        Some(subscriber)
+    };
+
+    let heading_aid =
+        (!args.heading_topic.is_empty()).then(|| Arc::new(Mutex::new(HeadingAid::new(&args))));
+    let _heading_subscriber = heading_aid.as_ref().map(|aid| {
+        let aid = aid.clone();
+        let source = args.heading_source;
+        let min_speed = args.heading_min_speed;
+        let topic = args.resolve_topic(&args.heading_topic);
+        info!("Aiding heading from {} ({:?})", topic, source);
+        session
+            .declare_subscriber(topic)
+            .callback(
+                move |sample| match source.decode(&sample.payload().to_bytes(), min_speed) {
+                    Ok(Some((stamp, heading))) => aid.lock().unwrap().measure(&stamp, heading),
+                    Ok(None) => {}
+                    Err(e) => warn!("Failed to decode external heading: {}", e),
+                },
+            )
+            .wait()
+            .unwrap()
+    });
+
+    // Latched alarms survive sensor restarts until cleared.
+    let alarm_topic = args.resolve_topic(&args.alarm_topic);
+    let alarms = args
+        .alarms
+        .then(|| Arc::new(Mutex::new(AlarmMonitor::new(&args))));
+    let _alarm_queryable = alarms.as_ref().map(|alarms| {
+        let alarms = alarms.clone();
+        let session_ = session.clone();
+        let topic = alarm_topic.clone();
+        info!(
+            "Publishing alarms on {}, cleared with queries on {}/clear",
+            alarm_topic, alarm_topic
+        );
+        session
+            .declare_queryable(format!("{alarm_topic}/*"))
+            .callback(move |query| {
+                let name = query.parameters().get("alarm");
+                match query.key_expr().as_str().rsplit('/').next() {
+                    Some("clear") => match alarms.lock().unwrap().clear(name) {
+                        Ok(cleared) => {
+                            for alarm in cleared {
+                                reply(&query, "edgefirst_imu/msg/Alarm", &alarm);
+                                publish(&session_, &topic, "edgefirst_imu/msg/Alarm", &alarm);
+                            }
+                        }
+                        Err(e) => reply_err(&query, &e),
+                    },
+                    Some("events") => match alarms.lock().unwrap().events(name) {
+                        Ok(events) => {
+                            for event in events {
+                                reply(&query, "edgefirst_imu/msg/AlarmEvent", &event);
+                            }
+                        }
+                        Err(e) => reply_err(&query, &e),
+                    },
+                    _ => reply_err(&query, "expected a clear or events query"),
+                }
+            })
+            .wait()
+            .unwrap()
+    });
+
+    // The tare reference survives sensor restarts.
+    let tare = args.tare.then(|| Arc::new(Mutex::new(Tare::new(&args))));
+    let _tare_queryable = tare.as_ref().map(|tare| {
+        let tare = tare.clone();
+        let key = args.resolve_topic(&args.tare_key);
+        info!("Answering tare commands on {}/*", key);
+        session
+            .declare_queryable(format!("{key}/*"))
+            .callback(move |query| {
+                let action = query.key_expr().as_str().rsplit('/').next().unwrap_or("");
+                match Tare::query(&tare, action) {
+                    Ok(state) => reply(&query, "edgefirst_imu/msg/TareState", &state),
+                    Err(e) => reply_err(&query, &e),
+                }
+            })
+            .wait()
+            .unwrap()
+    });
+
     let mut consecutive_fail_count = 0;
     while consecutive_fail_count < 3 && !SHUTDOWN.load(Ordering::SeqCst) {
-        let elapsed = run_imu(&args, session.clone());
+        let elapsed = run_imu(
+            &args,
+            session.clone(),
+            declination.clone(),
+            heading_aid.clone(),
+            alarms.clone(),
+            tare.clone(),
+            &filters,
+        );
         // considered a success if the IMU runs for more than the time limit
         if elapsed > SUCCESS_TIME_LIMIT {
             consecutive_fail_count = 0;
@@ -147,7 +363,15 @@ fn main() {
 // This function will reset and initialize the IMU, enable reports, and send
 // messages. If no message has been sent for while, the function will return.
 // The function returns total elapsed duration
-fn run_imu(args: &Args, session: Session) -> Duration {
+fn run_imu(
+    args: &Args,
+    session: Session,
+    declination: Arc<Declination>,
+    heading_aid: Option<Arc<Mutex<HeadingAid>>>,
+    alarms: Option<Arc<Mutex<AlarmMonitor>>>,
+    tare: Option<Arc<Mutex<Tare>>>,
+    filters: &Filters,
+) -> Duration {
     let fail_time_limit = Duration::from_millis(args.timeout);
     // Initializing the driver interface.
     debug!("Initializing driver wrapper with parameters:");
@@ -161,17 +385,208 @@ fn run_imu(args: &Args, session: Session) -> Duration {
         error!("Could not initialize driver: {:?}", e);
         return Duration::from_nanos(0);
     }
-    if let Err(e) = driver.enable_reports() {
+    let sample_interval = Duration::from_millis(args.sample_interval);
+    if let Err(e) = driver.enable_reports(
+        sample_interval,
+        Duration::from_millis(args.accel_interval),
+        Duration::from_millis(args.gyro_interval),
+    ) {
         error!("Could not initialize reports: {:?}", e);
         return Duration::from_nanos(0);
     }
+    let fusion = args.fusion != FusionAlgorithm::Off;
+    let mut reports = Vec::new();
+    if args.mag_monitor || (fusion && args.fusion_mag) {
+        reports.push((SENSOR_REPORTID_MAGNETIC_FIELD, driver::MAGNETOMETER_UPDATE));
+    }
+    if args.mag_monitor && args.mag_fallback {
+        reports.push((SENSOR_REPORTID_ROTATION_VECTOR_GAME, sample_interval));
+    }
+    for (report_id, interval) in reports {
+        if let Err(e) = driver.enable_report(report_id, interval) {
+            error!("Could not initialize reports: {:?}", e);
+            return Duration::from_nanos(0);
+        }
+    }
 
     info!("IMU Device Initialized");
+    if let Some(tare) = &tare {
+        tare.lock().unwrap().sensor_reset();
+    }
+
+    let topic = args.resolve_topic(&args.topic);
+    info!("Publishing IMU messages on {}", topic);
+    let relative_topic = args.resolve_topic(&args.relative_topic);
+    if tare.is_some() {
+        info!("Publishing tared orientation on {}", relative_topic);
+    }
+    let decimator = Mutex::new(Decimator::new(args.rate));
+    let delta_topic = args.resolve_topic(&args.delta_topic);
+    let delta = args.delta.then(|| {
+        info!("Publishing delta increments on {}", delta_topic);
+        Mutex::new(DeltaIntegrator::new(args))
+    });
+    let batch_topic = args.resolve_topic(&args.batch_topic);
+    let batcher = args.batch.then(|| {
+        info!("Publishing batched IMU messages on {}", batch_topic);
+        if args.batch_only {
+            info!("Not publishing individual samples on {}", topic);
+        }
+        Arc::new(Mutex::new(Batcher::new(args)))
+    });
+    // Flushes partial batches from the polling loop when the reports stop.
+    let batch_timer = batcher
+        .clone()
+        .map(|batcher| (batcher, session.clone(), batch_topic.clone()));
+    let outputs: Vec<_> = args
+        .output
+        .iter()
+        .map(|output| {
+            let topic = args.resolve_topic(&output.topic);
+            info!("Publishing IMU messages on {} at {} Hz", topic, output.rate);
+            (topic, Mutex::new(Decimator::new(output.rate)))
+        })
+        .collect();
+    let tf_topic = args.resolve_topic(&args.tf_topic);
+    let euler_topic = args.resolve_topic(&args.euler_topic);
+    if args.tf {
+        info!(
+            "Broadcasting {} -> {} transforms on {}",
+            args.tf_parent_frame, args.frame_id, tf_topic
+        );
+    }
+
+    let mag_topic = args.resolve_topic(&args.mag_topic);
+    let mag_monitor = Mutex::new(MagneticMonitor::new(args));
+    let alarm_topic = args.resolve_topic(&args.alarm_topic);
+
+    let stationary_topic = args.resolve_topic(&args.stationary_topic);
+    let stationary = args
+        .stationary
+        .then(|| Mutex::new(StationaryDetector::new(args)));
+
+    let vibration_topic = args.resolve_topic(&args.vibration_topic);
+    let vibration = args.vibration.then(|| {
+        info!("Publishing the vibration spectrum on {}", vibration_topic);
+        Mutex::new(VibrationAnalyzer::new(args))
+    });
+
+    let motion_topic = args.resolve_topic(&args.motion_topic);
+    let motion = args.motion.then(|| {
+        info!("Publishing the motion state on {}", motion_topic);
+        Mutex::new(MotionClassifier::new(args))
+    });
+
+    let odom_topic = args.resolve_topic(&args.odom_topic);
+    let odometry = args.odom.then(|| {
+        info!("Publishing dead-reckoning odometry on {}", odom_topic);
+        Mutex::new(DeadReckoning::new(args))
+    });
+
+    let raw_topic = args.resolve_topic(&args.raw_topic);
+    let filters = Mutex::new(filters.clone());
+    let raw_decimator = Mutex::new(Decimator::new(args.rate));
+    if args.filter_raw {
+        info!("Publishing unfiltered IMU messages on {}", raw_topic);
+    }
+
+    let fusion_topic = args.resolve_topic(&args.fusion_topic);
+    let ahrs = fusion.then(|| Mutex::new(Ahrs::new(args)));
+    let fusion_decimator = Mutex::new(Decimator::new(args.rate));
+    if fusion {
+        info!(
+            "Host-side {:?} fusion published on {}",
+            args.fusion,
+            if args.fusion_primary {
+                &topic
+            } else {
+                &fusion_topic
+            }
+        );
+    }
+
+    let preintegrated_topic = args.resolve_topic(&args.preintegrated_topic);
+    let preintegrator =
+        (!args.frame_topic.is_empty()).then(|| Arc::new(Mutex::new(Preintegrator::new(args))));
+    let _frame_subscriber = preintegrator.as_ref().map(|preintegrator| {
+        let preintegrator = preintegrator.clone();
+        let frame_topic = args.resolve_topic(&args.frame_topic);
+        info!(
+            "Preintegrating IMU between {} frames on {}",
+            frame_topic, preintegrated_topic
+        );
+        session
+            .declare_subscriber(frame_topic)
+            .callback(move |sample| {
+                match preintegration::frame_stamp(&sample.payload().to_bytes()) {
+                    Ok(stamp) => preintegrator.lock().unwrap().push_frame(stamp),
+                    Err(e) => warn!("Failed to decode frame header: {}", e),
+                }
+            })
+            .wait()
+            .unwrap()
+    });
+
+    let history = args
+        .rotation_query
+        .then(|| Arc::new(Mutex::new(RotationHistory::new(args))));
+    let _rotation_queryable = history.as_ref().map(|history| {
+        let history = history.clone();
+        let key = args.resolve_topic(&args.rotation_key);
+        info!("Answering ego-rotation queries on {}", key);
+        session
+            .declare_queryable(key)
+            .callback(move |query| {
+                let parameters = query.parameters();
+                let result =
+                    history::parse_interval(parameters.get("start"), parameters.get("end"))
+                        .and_then(|(start, end)| history.lock().unwrap().rotation(start, end));
+                match result {
+                    Ok(msg) => reply(&query, "edgefirst_imu/msg/EgoRotation", &msg),
+                    Err(e) => reply_err(&query, &e),
+                }
+            })
+            .wait()
+            .unwrap()
+    });
+
+    let fused_odom_topic = args.resolve_topic(&args.fused_odom_topic);
+    let wheel =
+        (!args.wheel_topic.is_empty()).then(|| Arc::new(Mutex::new(WheelOdometry::new(args))));
+    let _wheel_subscriber = wheel.as_ref().map(|wheel| {
+        let wheel = wheel.clone();
+        let source = args.wheel_source;
+        let wheel_topic = args.resolve_topic(&args.wheel_topic);
+        info!(
+            "Fusing wheel odometry from {} ({:?}) on {}",
+            wheel_topic, source, fused_odom_topic
+        );
+        session
+            .declare_subscriber(wheel_topic)
+            .callback(
+                move |sample| match source.decode(&sample.payload().to_bytes()) {
+                    Ok((stamp, speed, yaw_rate)) => {
+                        wheel.lock().unwrap().measure(&stamp, speed, yaw_rate)
+                    }
+                    Err(e) => warn!("Failed to decode wheel odometry: {}", e),
+                },
+            )
+            .wait()
+            .unwrap()
+    });
 
     let last_send = Arc::from(Mutex::from((Instant::now(), false)));
     let last_send_ = last_send.clone();
+    let tare_commands = tare.clone();
     let report_update_cb =
         move |imu_driver: &BNO08x<SpiInterface<SpiDevice, GpiodIn, GpiodOut>>| {
+            // After a sensor tare the driver is rebuilt and reads zeros until
+            // the accelerometer and gyroscope report again.
+            if imu_driver.report_update_time(SENSOR_REPORTID_ACCELEROMETER) == 0
+                || imu_driver.report_update_time(SENSOR_REPORTID_GYROSCOPE) == 0
+            {
+                return;
+            }
             info_span!("publish").in_scope(|| {
                 let [qi, qj, qk, qr] = imu_driver.rotation_quaternion().unwrap();
                 let [lin_ax, lin_ay, lin_az] = imu_driver.accelerometer().unwrap();
@@ -206,36 +621,201 @@ fn run_imu(args: &Args, session: Session) -> Duration {
                     }
                 };
 
-                let msg = sensor_msgs::IMU {
-                    header: std_msgs::Header {
-                        stamp,
-                        frame_id: "".to_owned(),
-                    },
-                    orientation: geometry_msgs::Quaternion {
-                        x: qi as f64,
-                        y: qj as f64,
-                        z: qk as f64,
-                        w: qr as f64,
-                    },
-                    orientation_covariance: [-1.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0],
-                    angular_velocity: geometry_msgs::Vector3 {
-                        x: ang_ax as f64,
-                        y: ang_ay as f64,
-                        z: ang_az as f64,
-                    },
-                    angular_velocity_covariance: [-1.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0],
-                    linear_acceleration: geometry_msgs::Vector3 {
-                        x: lin_ax as f64,
-                        y: lin_ay as f64,
-                        z: lin_az as f64,
-                    },
-                    linear_acceleration_covariance: [-1.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0],
+                let mut sample = Sample {
+                    stamp,
+                    orientation: Quat::from_ijkr([qi, qj, qk, qr]),
+                    angular_velocity: Vec3::from_f32([ang_ax, ang_ay, ang_az]),
+                    linear_acceleration: Vec3::from_f32([lin_ax, lin_ay, lin_az]),
+                    heading_accuracy: imu_driver.rotation_acc() as f64,
                 };
 
-                let buf = ZBytes::from(serde_cdr::serialize(&msg).unwrap());
-                let enc = Encoding::APPLICATION_CDR.with_schema("sensor_msgs/msg/Imu");
+                if args.mag_monitor {
+                    let game_rotation = args
+                        .mag_fallback
+                        .then(|| Quat::from_ijkr(imu_driver.game_rotation_quaternion().unwrap()));
+                    let check = mag_monitor.lock().unwrap().update(
+                        &sample.stamp,
+                        sample.orientation,
+                        game_rotation,
+                        Vec3::from_f32(imu_driver.mag_field().unwrap()),
+                        sample.heading_accuracy,
+                        declination.field(),
+                    );
+                    if let Some(check) = check {
+                        if check.changed && check.status.disturbed {
+                            warn!(
+                                "Magnetic disturbance detected: {:.1} uT, dip {:.1} deg",
+                                check.status.intensity, check.status.inclination
+                            );
+                        } else if check.changed {
+                            info!("Magnetic field clean");
+                        }
+                        if let Some(orientation) = check.orientation {
+                            sample.orientation = orientation;
+                        }
+                        if check.publish {
+                            publish(
+                                &session,
+                                &mag_topic,
+                                "edgefirst_imu/msg/MagneticStatus",
+                                &check.status,
+                            );
+                        }
+                    }
+                }
+
+                let declination = declination.degrees();
+
+                // Host-side fusion from the raw sensor frame readings.
+                let mut fused = ahrs.as_ref().and_then(|ahrs| {
+                    let mag = args
+                        .fusion_mag
+                        .then(|| Vec3::from_f32(imu_driver.mag_field().unwrap()));
+                    let orientation = ahrs.lock().unwrap().update(
+                        sample.angular_velocity,
+                        sample.linear_acceleration,
+                        mag,
+                    )?;
+                    Some(Sample {
+                        orientation,
+                        ..sample.clone()
+                    })
+                });
+                if args.fusion_primary {
+                    match fused.take() {
+                        Some(fused) => sample = fused,
+                        // Wait for the filter to initialize.
+                        None => return,
+                    }
+                }
+
+                to_output_frame(args, &mut sample, declination, heading_aid.as_deref());
+                if let Some(delta) = delta
+                    .as_ref()
+                    .and_then(|delta| delta.lock().unwrap().push(&sample))
+                {
+                    publish(&session, &delta_topic, "edgefirst_imu/msg/ImuDelta", &delta);
+                }
+                if let Some(alarms) = &alarms {
+                    for alarm in alarms.lock().unwrap().update(&sample) {
+                        publish(&session, &alarm_topic, "edgefirst_imu/msg/Alarm", &alarm);
+                    }
+                }
+                if let Some(spectrum) = vibration
+                    .as_ref()
+                    .and_then(|vibration| vibration.lock().unwrap().push(&sample))
+                {
+                    publish(
+                        &session,
+                        &vibration_topic,
+                        "edgefirst_imu/msg/VibrationSpectrum",
+                        &spectrum,
+                    );
+                }
+                if let Some(history) = &history {
+                    history.lock().unwrap().push(&sample);
+                }
+                if let Some(preintegrator) = &preintegrator {
+                    let mut preintegrator = preintegrator.lock().unwrap();
+                    preintegrator.push_sample(&sample);
+                    for msg in preintegrator.poll() {
+                        publish(
+                            &session,
+                            &preintegrated_topic,
+                            "edgefirst_imu/msg/PreintegratedImu",
+                            &msg,
+                        );
+                    }
+                }
+
+                let raw = args.filter_raw.then(|| sample.clone());
+                filters.lock().unwrap().apply(&mut sample);
+
+                if let Some(state) = stationary
+                    .as_ref()
+                    .and_then(|detector| detector.lock().unwrap().update(&sample))
+                {
+                    publish(
+                        &session,
+                        &stationary_topic,
+                        "edgefirst_imu/msg/Stationary",
+                        &state,
+                    );
+                }
+                if let Some(state) = motion
+                    .as_ref()
+                    .and_then(|motion| motion.lock().unwrap().update(&sample))
+                {
+                    publish(
+                        &session,
+                        &motion_topic,
+                        "edgefirst_imu/msg/MotionState",
+                        &state,
+                    );
+                }
+                if let Some(odom) = odometry
+                    .as_ref()
+                    .and_then(|odometry| odometry.lock().unwrap().update(&sample))
+                {
+                    publish(&session, &odom_topic, "nav_msgs/msg/Odometry", &odom);
+                }
+                if let Some(odom) = wheel
+                    .as_ref()
+                    .and_then(|wheel| wheel.lock().unwrap().update_imu(&sample))
+                {
+                    publish(&session, &fused_odom_topic, "nav_msgs/msg/Odometry", &odom);
+                }
+
+                if let Some(batch) = batcher
+                    .as_ref()
+                    .and_then(|batcher| batcher.lock().unwrap().push(&sample))
+                {
+                    publish(&session, &batch_topic, "edgefirst_imu/msg/ImuBatch", &batch);
+                }
+
+                for (topic, decimator) in &outputs {
+                    if let Some(output) = decimator.lock().unwrap().push(&sample) {
+                        let msg = output.to_imu(&args.frame_id);
+                        publish(&session, topic, "sensor_msgs/msg/Imu", &msg);
+                    }
+                }
+
+                if let Some(raw) = raw.and_then(|raw| raw_decimator.lock().unwrap().push(&raw)) {
+                    let msg = raw.to_imu(&args.frame_id);
+                    publish(&session, &raw_topic, "sensor_msgs/msg/Imu", &msg);
+                }
+
+                if let Some(mut fused) = fused {
+                    to_output_frame(args, &mut fused, declination, None);
+                    if let Some(fused) = fusion_decimator.lock().unwrap().push(&fused) {
+                        let msg = fused.to_imu(&args.frame_id);
+                        publish(&session, &fusion_topic, "sensor_msgs/msg/Imu", &msg);
+                    }
+                }
+
+                if let Some(sample) = decimator.lock().unwrap().push(&sample) {
+                    let msg = sample.to_imu(&args.frame_id);
+                    if !(args.batch && args.batch_only) {
+                        publish(&session, &topic, "sensor_msgs/msg/Imu", &msg);
+                    }
+
+                    if args.tf {
+                        let tf = orientation_transform(&msg, &args.tf_parent_frame);
+                        publish(&session, &tf_topic, "tf2_msgs/msg/TFMessage", &tf);
+                    }
+
+                    if args.euler {
+                        let euler = euler::euler_message(&sample, args, declination);
+                        publish(&session, &euler_topic, "edgefirst_imu/msg/Euler", &euler);
+                    }
+
+                    if let Some(tare) = &tare {
+                        let relative = tare.lock().unwrap().apply(&sample);
+                        let msg = relative.to_imu(&args.frame_id);
+                        publish(&session, &relative_topic, "sensor_msgs/msg/Imu", &msg);
+                    }
+                }
 
-                session.put(&args.topic, buf).encoding(enc).wait().unwrap();
                 let mut last_send_locked = last_send.lock().unwrap();
                 *(last_send_locked) = (Instant::now(), true);
             });
@@ -243,11 +823,8 @@ fn run_imu(args: &Args, session: Session) -> Duration {
             args.tracy.then(frame_mark);
         };
 
-    driver.imu_driver.add_sensor_report_callback(
-        SENSOR_REPORTID_ROTATION_VECTOR,
-        String::from("report_update_cb"),
-        report_update_cb,
-    );
+    let report_update_cb = Arc::new(report_update_cb);
+    add_report_callback(&mut driver, &report_update_cb);
     let start = Instant::now();
     loop {
         // Check for shutdown signal
@@ -257,6 +834,23 @@ fn run_imu(args: &Args, session: Session) -> Duration {
         }
 
         let _msg_count = driver.imu_driver.handle_messages(2, 10);
+        let pending = tare_commands
+            .as_ref()
+            .map(|tare| tare.lock().unwrap().take_pending())
+            .unwrap_or_default();
+        if !pending.is_empty() {
+            let mut sh2 = Sh2::new(driver);
+            for command in pending {
+                command.run(&mut sh2, tare_commands.as_ref().unwrap());
+            }
+            driver = sh2.into_driver();
+            add_report_callback(&mut driver, &report_update_cb);
+        }
+        if let Some((batcher, session, topic)) = &batch_timer {
+            if let Some(batch) = batcher.lock().unwrap().poll() {
+                publish(session, topic, "edgefirst_imu/msg/ImuBatch", &batch);
+            }
+        }
         let lock = last_send_.lock().unwrap();
         let last_msg_time = lock.0;
         let started = lock.1;
@@ -279,6 +873,100 @@ fn run_imu(args: &Args, session: Session) -> Duration {
     }
 }
 
+/// Adds the sample callback, shared so it can be added again to the driver
+/// rebuilt after a sensor tare.
+fn add_report_callback<'a>(
+    driver: &mut Driver<'a>,
+    callback: &Arc<impl Fn(&BNO08x<'a, SpiInterface<SpiDevice, GpiodIn, GpiodOut>>) + 'a>,
+) {
+    let callback = callback.clone();
+    driver.imu_driver.add_sensor_report_callback(
+        SENSOR_REPORTID_ROTATION_VECTOR,
+        String::from("report_update_cb"),
+        move |imu_driver| callback(imu_driver),
+    );
+}
+
+/// Writes the sensor orientation FRS record, or only shows it with
+/// --dry-run.
+fn configure(args: &Args) {
+    let q = args.configure_orientation.rotation.normalize();
+    let result =
+        frs::open(args).and_then(|mut sh2| frs::configure_orientation(&mut sh2, &q, args.dry_run));
+    if let Err(e) = result {
+        error!("ERROR: FRS records not updated: {}", e);
+        std::process::exit(1);
+    }
+}
+
+/// Applies the mounting rotation, the coordinate convention, the true
+/// north correction and the external heading aiding to a sensor frame
+/// sample.
+fn to_output_frame(
+    args: &Args,
+    sample: &mut Sample,
+    declination: f64,
+    heading_aid: Option<&Mutex<HeadingAid>>,
+) {
+    args.mounting.apply(sample);
+    args.convention.apply(sample);
+    if args.true_north {
+        sample.orientation = args
+            .convention
+            .rotate_heading(sample.orientation, declination);
+    }
+    if let Some(aid) = heading_aid {
+        aid.lock().unwrap().apply(sample);
+    }
+}
+
+fn publish<T: Serialize>(session: &Session, topic: &str, schema: &str, msg: &T) {
+    let buf = ZBytes::from(serde_cdr::serialize(msg).unwrap());
+    let enc = Encoding::APPLICATION_CDR.with_schema(schema);
+    session.put(topic, buf).encoding(enc).wait().unwrap();
+}
+
+fn reply<T: Serialize>(query: &Query, schema: &str, msg: &T) {
+    let buf = ZBytes::from(serde_cdr::serialize(msg).unwrap());
+    let enc = Encoding::APPLICATION_CDR.with_schema(schema);
+    if let Err(e) = query
+        .reply(query.key_expr().clone(), buf)
+        .encoding(enc)
+        .wait()
+    {
+        warn!("Failed to reply to {}: {}", query.key_expr(), e);
+    }
+}
+
+fn reply_err(query: &Query, message: &str) {
+    debug!("Rejecting query {}: {}", query.selector(), message);
+    if let Err(e) = query.reply_err(message.to_owned()).wait() {
+        warn!("Failed to reply to {}: {}", query.key_expr(), e);
+    }
+}
+
+/// Builds the transform from `parent_frame` to the IMU frame carrying the
+/// orientation of `msg` with no translation.
+fn orientation_transform(msg: &sensor_msgs::IMU, parent_frame: &str) -> TFMessage {
+    TFMessage {
+        transforms: vec![geometry_msgs::TransformStamped {
+            header: std_msgs::Header {
+                stamp: msg.header.stamp.clone(),
+                frame_id: parent_frame.to_owned(),
+            },
+            child_frame_id: msg.header.frame_id.clone(),
+            transform: geometry_msgs::Transform {
+                translation: geometry_msgs::Vector3 {
+                    x: 0.0,
+                    y: 0.0,
+                    z: 0.0,
+                },
+                rotation: msg.orientation,
+            },
+        }],
+    }
+}
+
 fn timestamp() -> Result<builtin_interfaces::Time, TimestampError> {
     let duration = SystemTime::now()
         .duration_since(UNIX_EPOCH)
@@ -0,0 +1,296 @@
+// Copyright 2025 Au-Zone Technologies Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Vehicle motion state classification.
+//!
+//! Signals averaged over a sliding window are compared against thresholds
+//! to pick one motion state, in order of priority: stationary, bumpy
+//! surface, turning, braking, accelerating, on incline and moving
+//! straight. Two forms of hysteresis keep the state from flickering: the
+//! current state is kept until its signal drops below a fraction of its
+//! threshold, and a new state must persist for the hold time before it is
+//! reported.
+use crate::{
+    args::Args, convention::Convention, math::Vec3, msgs::MotionState, sample::Sample,
+    stationary::StationaryDetector,
+};
+use edgefirst_schemas::{builtin_interfaces, std_msgs};
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+/// Fraction of its threshold below which the current state is left.
+const EXIT_RATIO: f64 = 0.7;
+
+/// Interval at which the state is republished while it does not change.
+const STATUS_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Window averages the classification is based on.
+#[derive(Debug, Clone, Copy, Default)]
+struct Signals {
+    /// Yaw rate in rad/s, positive turning left.
+    yaw_rate: f64,
+    /// Horizontal acceleration along the heading in m/s², which excludes
+    /// gravity even on an incline.
+    longitudinal: f64,
+    /// Nose up pitch in degrees.
+    pitch: f64,
+    /// Standard deviation of the body vertical acceleration in m/s².
+    vertical_std: f64,
+}
+
+pub struct MotionClassifier {
+    frame_id: String,
+    convention: Convention,
+    window: u64,
+    hold: u64,
+    turn_rate: f64,
+    accel: f64,
+    incline: f64,
+    bump_std: f64,
+    detector: StationaryDetector,
+
+    /// `(stamp, yaw rate, longitudinal, pitch, vertical acceleration)`.
+    samples: VecDeque<(u64, [f64; 4])>,
+    state: u8,
+    since: builtin_interfaces::Time,
+    /// Candidate state and the stamp it first appeared at.
+    candidate: Option<(u8, u64)>,
+    last_status: Option<Instant>,
+}
+
+impl MotionClassifier {
+    pub fn new(args: &Args) -> Self {
+        Self {
+            frame_id: args.frame_id.clone(),
+            convention: args.convention,
+            window: (args.motion_window * 1e9) as u64,
+            hold: (args.motion_hold * 1e9) as u64,
+            turn_rate: args.motion_turn_rate,
+            accel: args.motion_accel,
+            incline: args.motion_incline,
+            bump_std: args.motion_bump_std,
+            detector: StationaryDetector::new(args),
+            samples: VecDeque::new(),
+            state: MotionState::STATIONARY,
+            since: builtin_interfaces::Time::new(0, 0),
+            candidate: None,
+            last_status: None,
+        }
+    }
+
+    /// Adds an output frame sample and returns the state when it changed or
+    /// is due for its periodic republish.
+    pub fn update(&mut self, sample: &Sample) -> Option<MotionState> {
+        self.step(Instant::now(), sample)
+    }
+
+    fn step(&mut self, now: Instant, sample: &Sample) -> Option<MotionState> {
+        let stamp = sample.stamp.to_nanos();
+        self.detector.step(now, sample);
+
+        // World vertical and nose up are +Z in ENU and -Z in NED.
+        let up = match self.convention {
+            Convention::Enu => 1.0,
+            Convention::Ned => -1.0,
+        };
+        let q = sample.orientation;
+        let forward = q.rotate(Vec3::new(1.0, 0.0, 0.0));
+        let heading = Vec3::new(forward.x, forward.y, 0.0)
+            .normalize()
+            .unwrap_or_default();
+        let world = q.rotate(sample.linear_acceleration);
+        let vertical = q.conjugate().rotate(Vec3::new(0.0, 0.0, up));
+        self.samples.push_back((
+            stamp,
+            [
+                up * q.rotate(sample.angular_velocity).z,
+                world.dot(&heading),
+                (up * forward.z).clamp(-1.0, 1.0).asin().to_degrees(),
+                sample.linear_acceleration.dot(&vertical),
+            ],
+        ));
+        while self
+            .samples
+            .front()
+            .is_some_and(|(t, _)| stamp.saturating_sub(*t) > self.window)
+        {
+            self.samples.pop_front();
+        }
+        let signals = self.signals();
+
+        let classified = self.classify(&signals);
+        let mut changed = false;
+        if classified == self.state {
+            self.candidate = None;
+        } else {
+            let first = match self.candidate {
+                Some((candidate, first)) if candidate == classified => first,
+                _ => {
+                    self.candidate = Some((classified, stamp));
+                    stamp
+                }
+            };
+            if stamp.saturating_sub(first) >= self.hold {
+                self.state = classified;
+                self.since = sample.stamp.clone();
+                self.candidate = None;
+                changed = true;
+            }
+        }
+
+        let publish = changed
+            || self
+                .last_status
+                .is_none_or(|t| now.duration_since(t) >= STATUS_INTERVAL);
+        if !publish {
+            return None;
+        }
+        self.last_status = Some(now);
+
+        Some(MotionState {
+            header: std_msgs::Header {
+                stamp: sample.stamp.clone(),
+                frame_id: self.frame_id.clone(),
+            },
+            state: self.state,
+            since: self.since.clone(),
+            yaw_rate: signals.yaw_rate,
+            longitudinal_acceleration: signals.longitudinal,
+            pitch: signals.pitch,
+            vertical_accel_std: signals.vertical_std,
+        })
+    }
+
+    fn signals(&self) -> Signals {
+        let n = self.samples.len().max(1) as f64;
+        let mean = |i: usize| self.samples.iter().map(|(_, s)| s[i]).sum::<f64>() / n;
+        let vertical = mean(3);
+        let variance = self
+            .samples
+            .iter()
+            .map(|(_, s)| (s[3] - vertical).powi(2))
+            .sum::<f64>()
+            / n;
+        Signals {
+            yaw_rate: mean(0),
+            longitudinal: mean(1),
+            pitch: mean(2),
+            vertical_std: variance.sqrt(),
+        }
+    }
+
+    fn classify(&self, signals: &Signals) -> u8 {
+        // The threshold of the current state is relaxed so it is only left
+        // once the signal has clearly dropped.
+        let above = |state: u8, value: f64, threshold: f64| {
+            let ratio = if state == self.state { EXIT_RATIO } else { 1.0 };
+            value > threshold * ratio
+        };
+        if self.detector.is_stationary() {
+            MotionState::STATIONARY
+        } else if above(MotionState::BUMPY, signals.vertical_std, self.bump_std) {
+            MotionState::BUMPY
+        } else if above(MotionState::TURNING_LEFT, signals.yaw_rate, self.turn_rate) {
+            MotionState::TURNING_LEFT
+        } else if above(
+            MotionState::TURNING_RIGHT,
+            -signals.yaw_rate,
+            self.turn_rate,
+        ) {
+            MotionState::TURNING_RIGHT
+        } else if above(MotionState::BRAKING, -signals.longitudinal, self.accel) {
+            MotionState::BRAKING
+        } else if above(MotionState::ACCELERATING, signals.longitudinal, self.accel) {
+            MotionState::ACCELERATING
+        } else if above(MotionState::INCLINE, signals.pitch.abs(), self.incline) {
+            MotionState::INCLINE
+        } else {
+            MotionState::STRAIGHT
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::math::Quat;
+    use clap::Parser;
+
+    #[test]
+    fn test_turn_with_hold() {
+        let args = Args::parse_from(["edgefirst-imu", "--motion"]);
+        let mut classifier = MotionClassifier::new(&args);
+        let mut sample = Sample {
+            stamp: builtin_interfaces::Time::new(0, 0),
+            orientation: Quat::new(1.0, 0.0, 0.0, 0.0),
+            angular_velocity: Vec3::default(),
+            linear_acceleration: Vec3::new(0.0, 0.0, 9.81),
+            heading_accuracy: 0.0,
+        };
+        let start = Instant::now();
+        let run = |classifier: &mut MotionClassifier, sample: &mut Sample, from: u64, to: u64| {
+            let mut state = None;
+            for i in from..to {
+                sample.stamp = builtin_interfaces::Time::from_nanos(1_000_000_000 + i * 5_000_000);
+                state = classifier
+                    .step(start + Duration::from_millis(5 * i), sample)
+                    .or(state);
+            }
+            state.unwrap().state
+        };
+        assert_eq!(
+            run(&mut classifier, &mut sample, 0, 200),
+            MotionState::STATIONARY
+        );
+
+        // Turning right at 0.3 rad/s while vibrating enough to be moving.
+        sample.angular_velocity = Vec3::new(0.0, 0.0, -0.3);
+        let mut state = None;
+        for i in 200..400u64 {
+            sample.linear_acceleration.x = if i % 2 == 0 { 0.2 } else { -0.2 };
+            sample.stamp = builtin_interfaces::Time::from_nanos(1_000_000_000 + i * 5_000_000);
+            let msg = classifier.step(start + Duration::from_millis(5 * i), &sample);
+            if let Some(msg) = msg.filter(|msg| msg.state != MotionState::STATIONARY) {
+                state.get_or_insert((i, msg.state));
+            }
+        }
+        let (i, state) = state.unwrap();
+        assert_eq!(state, MotionState::TURNING_RIGHT);
+        // Not before the hold time after leaving rest.
+        assert!(i >= 260, "{i}");
+        assert_eq!(
+            run(&mut classifier, &mut sample, 400, 600),
+            MotionState::TURNING_RIGHT
+        );
+    }
+
+    #[test]
+    fn test_stamp_going_backwards() {
+        let args = Args::parse_from(["edgefirst-imu", "--motion"]);
+        let mut classifier = MotionClassifier::new(&args);
+        let mut sample = Sample {
+            stamp: builtin_interfaces::Time::new(10, 0),
+            orientation: Quat::new(1.0, 0.0, 0.0, 0.0),
+            angular_velocity: Vec3::new(0.0, 0.0, 0.3),
+            linear_acceleration: Vec3::new(0.0, 0.0, 9.81),
+            heading_accuracy: 0.0,
+        };
+        let start = Instant::now();
+        classifier.step(start, &sample);
+        // A clock step back must not underflow the window or hold time.
+        let candidate = classifier.candidate;
+        sample.stamp = builtin_interfaces::Time::new(9, 0);
+        let msg = classifier.step(start + Duration::from_millis(5), &sample);
+        // Both samples stay in the window instead of the older one being
+        // dropped as out of range.
+        assert_eq!(classifier.samples.len(), 2);
+        // The candidate keeps its first stamp and has not been held long
+        // enough to be reported.
+        assert_eq!(classifier.candidate, candidate);
+        assert!(candidate.is_some_and(|(state, _)| state != MotionState::STATIONARY));
+        assert_eq!(classifier.state, MotionState::STATIONARY);
+        assert!(msg.is_none());
+    }
+}
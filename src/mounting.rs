@@ -0,0 +1,169 @@
+// Copyright 2025 Au-Zone Technologies Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Host-side mounting extrinsics.
+//!
+//! The mounting rotation takes vectors from the sensor frame to the body
+//! frame. It is applied on the host so one configuration can be used with
+//! any module orientation without rewriting the sensor orientation FRS
+//! record with `--configure`.
+use crate::{math::Quat, sample::Sample};
+use std::{f64::consts::FRAC_1_SQRT_2, str::FromStr};
+
+/// Tolerance used when validating that a matrix or quaternion describes a
+/// proper rotation.
+const ROTATION_TOLERANCE: f64 = 1e-3;
+
+/// Mounting rotation, parsed from one of the following forms:
+///
+/// - `quat:x,y,z,w` unit quaternion in the `geometry_msgs` order
+/// - `rpy:roll,pitch,yaw` fixed-axis angles in degrees
+/// - `matrix:r00,r01,r02,r10,r11,r12,r20,r21,r22` row-major rotation matrix
+///
+/// Quaternions must have unit norm and matrices must be orthonormal with a
+/// determinant of +1, so a reflection or a typo is rejected at startup
+/// rather than silently distorting the published data.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Mounting {
+    /// Rotation from the sensor frame to the body frame.
+    rotation: Quat,
+}
+
+impl Mounting {
+    /// Re-expresses a sample in the body frame.
+    pub fn apply(&self, sample: &mut Sample) {
+        sample.orientation = (sample.orientation * self.rotation.conjugate()).normalize();
+        sample.angular_velocity = self.rotation.rotate(sample.angular_velocity);
+        sample.linear_acceleration = self.rotation.rotate(sample.linear_acceleration);
+    }
+}
+
+impl FromStr for Mounting {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (kind, values) = s
+            .split_once(':')
+            .ok_or_else(|| format!("expected quat:, rpy: or matrix: prefix in {s:?}"))?;
+        let values = values
+            .split(',')
+            .map(|v| v.trim().parse::<f64>())
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("invalid number in {s:?}: {e}"))?;
+
+        let rotation = match (kind.trim(), values.as_slice()) {
+            ("quat", &[x, y, z, w]) => {
+                let q = Quat::new(w, x, y, z);
+                if (q.norm() - 1.0).abs() > ROTATION_TOLERANCE {
+                    return Err(format!("quaternion norm {} is not 1", q.norm()));
+                }
+                q.normalize()
+            }
+            ("rpy", &[roll, pitch, yaw]) => {
+                Quat::from_rpy(roll.to_radians(), pitch.to_radians(), yaw.to_radians())
+            }
+            ("matrix", m) if m.len() == 9 => {
+                let m = [[m[0], m[1], m[2]], [m[3], m[4], m[5]], [m[6], m[7], m[8]]];
+                validate_rotation(&m)?;
+                Quat::from_matrix(&m)
+            }
+            ("quat", _) => return Err("quat: expects 4 values (x,y,z,w)".to_owned()),
+            ("rpy", _) => return Err("rpy: expects 3 values (roll,pitch,yaw)".to_owned()),
+            ("matrix", _) => return Err("matrix: expects 9 values (row-major)".to_owned()),
+            (kind, _) => return Err(format!("unknown mounting format {kind:?}")),
+        };
+
+        Ok(Self { rotation })
+    }
+}
+
+/// Sensor orientation FRS record written by `--configure`, parsed from a
+/// preset or any of the [`Mounting`] forms:
+///
+/// - `maivin2`, -90 degrees about X, the record of the Maivin 2 board
+/// - `raivin`, the same board layout as the Maivin 2
+/// - `identity`, the sensor frame unchanged
+///
+/// Like the mounting, the record rotates vectors from the sensor frame to
+/// the frame the sensor reports in.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SensorOrientation {
+    pub rotation: Quat,
+}
+
+impl FromStr for SensorOrientation {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let rotation = match s.trim() {
+            "maivin2" | "raivin" => Quat::new(FRAC_1_SQRT_2, -FRAC_1_SQRT_2, 0.0, 0.0),
+            "identity" => Quat::new(1.0, 0.0, 0.0, 0.0),
+            _ => {
+                s.parse::<Mounting>()
+                    .map_err(|e| format!("{e}; presets are maivin2, raivin and identity"))?
+                    .rotation
+            }
+        };
+        Ok(Self { rotation })
+    }
+}
+
+/// Checks that `m` is orthonormal with a determinant of +1.
+fn validate_rotation(m: &[[f64; 3]; 3]) -> Result<(), String> {
+    for i in 0..3 {
+        for j in 0..3 {
+            let dot: f64 = (0..3).map(|k| m[k][i] * m[k][j]).sum();
+            let expected = if i == j { 1.0 } else { 0.0 };
+            if (dot - expected).abs() > ROTATION_TOLERANCE {
+                return Err(format!(
+                    "matrix is not orthonormal (column {i} . column {j} = {dot})"
+                ));
+            }
+        }
+    }
+
+    let det = m[0][0] * (m[1][1] * m[2][2] - m[1][2] * m[2][1])
+        - m[0][1] * (m[1][0] * m[2][2] - m[1][2] * m[2][0])
+        + m[0][2] * (m[1][0] * m[2][1] - m[1][1] * m[2][0]);
+    if (det - 1.0).abs() > ROTATION_TOLERANCE {
+        return Err(format!(
+            "matrix determinant is {det}, expected +1 (reflections are not rotations)"
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::math::Vec3;
+
+    #[test]
+    fn test_matrix_matches_rpy() {
+        // 90 degrees of yaw: sensor X points along body Y.
+        let m: Mounting = "matrix:0,-1,0,1,0,0,0,0,1".parse().unwrap();
+        let r: Mounting = "rpy:0,0,90".parse().unwrap();
+        let v = Vec3::new(1.0, 0.0, 0.0);
+        let (a, b) = (m.rotation.rotate(v), r.rotation.rotate(v));
+        assert!((a.x - b.x).abs() < 1e-9 && (a.y - b.y).abs() < 1e-9);
+        assert!((a.y - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_rejects_improper_rotations() {
+        assert!("matrix:1,0,0,0,1,0,0,0,-1".parse::<Mounting>().is_err());
+        assert!("matrix:2,0,0,0,1,0,0,0,1".parse::<Mounting>().is_err());
+        assert!("quat:0,0,0,2".parse::<Mounting>().is_err());
+        assert!("rpy:0,0".parse::<Mounting>().is_err());
+    }
+
+    #[test]
+    fn test_sensor_orientation_presets() {
+        let maivin2: SensorOrientation = "maivin2".parse().unwrap();
+        let rpy: SensorOrientation = "rpy:-90,0,0".parse().unwrap();
+        let (a, b) = (maivin2.rotation, rpy.rotation);
+        assert!((a.w - b.w).abs() < 1e-9 && (a.x - b.x).abs() < 1e-9);
+        assert_eq!("raivin".parse::<SensorOrientation>().unwrap(), maivin2);
+        assert!("maivin3".parse::<SensorOrientation>().is_err());
+    }
+}
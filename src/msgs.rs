@@ -0,0 +1,478 @@
+// Copyright 2025 Au-Zone Technologies Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Message types published by the IMU service that are not provided by
+//! `edgefirst-schemas`. Field order matches the ROS 2 message definitions so
+//! the CDR encoding is compatible with ROS 2 consumers.
+use edgefirst_schemas::{builtin_interfaces, geometry_msgs, sensor_msgs, serde_cdr, std_msgs};
+use serde::{
+    de::{self, SeqAccess, Visitor},
+    ser::SerializeTuple,
+    Deserialize, Deserializer, Serialize, Serializer,
+};
+use std::fmt;
+
+/// Covariance matrix marking the corresponding field as unknown.
+pub const UNKNOWN_COVARIANCE: [f64; 9] = [-1.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0];
+
+/// `tf2_msgs/msg/TFMessage`
+#[derive(Serialize, Deserialize, PartialEq, Clone, Debug)]
+pub struct TFMessage {
+    pub transforms: Vec<geometry_msgs::TransformStamped>,
+}
+
+/// Row-major 6x6 covariance of `(x, y, z, rotation about x, y, z)`.
+///
+/// Serde only derives arrays of up to 32 elements, so the fixed size array
+/// is serialized by hand, without a length prefix as ROS 2 expects.
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub struct Covariance(pub [f64; 36]);
+
+impl Default for Covariance {
+    fn default() -> Self {
+        Self([0.0; 36])
+    }
+}
+
+impl Serialize for Covariance {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut tuple = serializer.serialize_tuple(36)?;
+        for value in &self.0 {
+            tuple.serialize_element(value)?;
+        }
+        tuple.end()
+    }
+}
+
+impl<'de> Deserialize<'de> for Covariance {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct CovarianceVisitor;
+
+        impl<'de> Visitor<'de> for CovarianceVisitor {
+            type Value = Covariance;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("36 covariance values")
+            }
+
+            fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Covariance, A::Error> {
+                let mut values = [0.0; 36];
+                for (i, value) in values.iter_mut().enumerate() {
+                    *value = seq
+                        .next_element()?
+                        .ok_or_else(|| de::Error::invalid_length(i, &self))?;
+                }
+                Ok(Covariance(values))
+            }
+        }
+
+        deserializer.deserialize_tuple(36, CovarianceVisitor)
+    }
+}
+
+/// `geometry_msgs/msg/PoseWithCovariance`
+#[derive(Serialize, Deserialize, PartialEq, Clone, Debug)]
+pub struct PoseWithCovariance {
+    pub pose: geometry_msgs::Pose,
+    pub covariance: Covariance,
+}
+
+/// `geometry_msgs/msg/TwistWithCovariance`
+#[derive(Serialize, Deserialize, PartialEq, Clone, Debug)]
+pub struct TwistWithCovariance {
+    pub twist: geometry_msgs::Twist,
+    pub covariance: Covariance,
+}
+
+/// `nav_msgs/msg/Odometry`: pose in `header.frame_id` and twist in
+/// `child_frame_id`.
+#[derive(Serialize, Deserialize, PartialEq, Clone, Debug)]
+pub struct Odometry {
+    pub header: std_msgs::Header,
+    pub child_frame_id: String,
+    pub pose: PoseWithCovariance,
+    pub twist: TwistWithCovariance,
+}
+
+/// `edgefirst_imu/msg/Euler`: orientation as Euler angles and compass
+/// heading. All angles are in degrees.
+#[derive(Serialize, Deserialize, PartialEq, Clone, Debug)]
+pub struct Euler {
+    pub header: std_msgs::Header,
+    /// Rotation about X.
+    pub roll: f64,
+    /// Rotation about Y.
+    pub pitch: f64,
+    /// Rotation about Z.
+    pub yaw: f64,
+    /// Heading clockwise from magnetic north in `[0, 360)`.
+    pub magnetic_heading: f64,
+    /// Heading clockwise from true north in `[0, 360)`.
+    pub true_heading: f64,
+    /// Magnetic declination applied to the true heading, east positive.
+    pub declination: f64,
+    /// Heading accuracy estimate reported by the sensor.
+    pub accuracy: f64,
+    /// Intrinsic rotation order of roll, pitch and yaw, e.g. `zyx`.
+    pub order: String,
+}
+
+/// `edgefirst_imu/msg/MagneticStatus`: magnetic disturbance state and the
+/// measurements it is derived from.
+#[derive(Serialize, Deserialize, PartialEq, Clone, Debug)]
+pub struct MagneticStatus {
+    pub header: std_msgs::Header,
+    /// The field is disturbed and the rotation vector heading is unreliable.
+    pub disturbed: bool,
+    /// Measured field strength in µT.
+    pub intensity: f64,
+    /// Expected field strength in µT.
+    pub expected_intensity: f64,
+    /// Measured dip angle in degrees, positive downward.
+    pub inclination: f64,
+    /// Expected dip angle in degrees, positive downward.
+    pub expected_inclination: f64,
+    /// Rotation vector heading accuracy estimate in degrees.
+    pub heading_accuracy: f64,
+    /// The published orientation comes from the game rotation vector.
+    pub game_rotation_fallback: bool,
+}
+
+/// `edgefirst_imu/msg/Stationary`: zero-velocity state and the residual
+/// gyroscope bias estimated while stationary.
+#[derive(Serialize, Deserialize, PartialEq, Clone, Debug)]
+pub struct Stationary {
+    pub header: std_msgs::Header,
+    /// The body is at rest.
+    pub stationary: bool,
+    /// Time of the last change of `stationary`.
+    pub since: builtin_interfaces::Time,
+    /// Standard deviation of the acceleration over the window in m/s².
+    pub accel_std: f64,
+    /// Standard deviation of the angular velocity over the window in rad/s.
+    pub gyro_std: f64,
+    /// Residual gyroscope bias in rad/s in the published body frame, zero
+    /// until the first stationary window.
+    pub gyro_bias: geometry_msgs::Vector3,
+    /// `gyro_bias` has been estimated.
+    pub gyro_bias_valid: bool,
+}
+
+/// `edgefirst_imu/msg/MotionState`: discrete vehicle motion state and the
+/// window averages it was classified from.
+#[derive(Serialize, Deserialize, PartialEq, Clone, Debug)]
+pub struct MotionState {
+    pub header: std_msgs::Header,
+    /// One of the state constants.
+    pub state: u8,
+    /// Time of the last change of `state`.
+    pub since: builtin_interfaces::Time,
+    /// Yaw rate in rad/s, positive turning left.
+    pub yaw_rate: f64,
+    /// Horizontal acceleration along the heading in m/s².
+    pub longitudinal_acceleration: f64,
+    /// Nose up pitch in degrees.
+    pub pitch: f64,
+    /// Standard deviation of the vertical acceleration in m/s².
+    pub vertical_accel_std: f64,
+}
+
+impl MotionState {
+    pub const STATIONARY: u8 = 0;
+    pub const STRAIGHT: u8 = 1;
+    pub const TURNING_LEFT: u8 = 2;
+    pub const TURNING_RIGHT: u8 = 3;
+    pub const ACCELERATING: u8 = 4;
+    pub const BRAKING: u8 = 5;
+    pub const INCLINE: u8 = 6;
+    pub const BUMPY: u8 = 7;
+}
+
+/// `edgefirst_imu/msg/Alarm`: state of one safety alarm.
+#[derive(Serialize, Deserialize, PartialEq, Clone, Debug)]
+pub struct Alarm {
+    pub header: std_msgs::Header,
+    /// One of the alarm constants.
+    pub alarm: u8,
+    /// One of the severity constants.
+    pub severity: u8,
+    /// The alarm condition is currently met.
+    pub active: bool,
+    /// The alarm was raised and has not been cleared.
+    pub latched: bool,
+    /// Time the alarm was last raised.
+    pub since: builtin_interfaces::Time,
+    /// Value that last met the condition: degrees for tilt and tip-over,
+    /// g for free-fall and impact.
+    pub value: f64,
+    /// Threshold the value was compared against, in the same unit.
+    pub threshold: f64,
+}
+
+impl Alarm {
+    pub const TILT: u8 = 0;
+    pub const TIP_OVER: u8 = 1;
+    pub const FREE_FALL: u8 = 2;
+    pub const IMPACT: u8 = 3;
+
+    pub const WARNING: u8 = 1;
+    pub const CRITICAL: u8 = 2;
+}
+
+/// `edgefirst_imu/msg/AlarmEvent`: an alarm as raised with the samples of
+/// the pre and post-event windows around it.
+#[derive(Serialize, Deserialize, PartialEq, Clone, Debug)]
+pub struct AlarmEvent {
+    pub alarm: Alarm,
+    /// Samples in time order, in the published body frame.
+    pub samples: Vec<ImuBatchSample>,
+}
+
+/// `edgefirst_imu/msg/VibrationSpectrum`: acceleration spectrum summary of
+/// one publish period.
+#[derive(Serialize, Deserialize, PartialEq, Clone, Debug)]
+pub struct VibrationSpectrum {
+    /// Stamp of the last sample of the period and the IMU frame ID.
+    pub header: std_msgs::Header,
+    /// Measured sample rate in Hz.
+    pub sample_rate: f64,
+    /// Width of one frequency bin in Hz.
+    pub resolution: f64,
+    /// Samples per FFT segment.
+    pub window_size: u32,
+    /// Number of segments averaged.
+    pub segments: u32,
+    /// Lower and upper edge of each band in Hz.
+    pub band_low: Vec<f64>,
+    pub band_high: Vec<f64>,
+    pub x: VibrationAxis,
+    pub y: VibrationAxis,
+    pub z: VibrationAxis,
+}
+
+/// Spectrum summary of one accelerometer axis.
+#[derive(Serialize, Deserialize, PartialEq, Clone, Debug)]
+pub struct VibrationAxis {
+    /// Acceleration RMS in m/s² excluding the mean.
+    pub rms: f64,
+    /// Acceleration variance in (m/s²)² within each band.
+    pub band_energy: Vec<f64>,
+    /// Frequency in Hz of the strongest bin above DC.
+    pub peak_frequency: f64,
+    /// Power spectral density of the strongest bin in (m/s²)²/Hz.
+    pub peak_density: f64,
+}
+
+/// `edgefirst_imu/msg/TareState`: zero reference of the relative
+/// orientation output.
+#[derive(Serialize, Deserialize, PartialEq, Clone, Debug)]
+pub struct TareState {
+    pub header: std_msgs::Header,
+    /// One of the mode constants.
+    pub mode: u8,
+    /// World frame rotation applied to the orientation.
+    pub offset: geometry_msgs::Quaternion,
+    /// The reference is saved and restored at startup.
+    pub persisted: bool,
+}
+
+impl TareState {
+    pub const NONE: u8 = 0;
+    pub const ALL: u8 = 1;
+    pub const YAW: u8 = 2;
+}
+
+/// `edgefirst_imu/msg/ImuDelta`: integrated rotation and velocity
+/// increments over one output interval, for inertial navigation consumers.
+#[derive(Serialize, Deserialize, PartialEq, Clone, Debug)]
+pub struct ImuDelta {
+    /// Stamp of the end of the interval and the IMU frame ID.
+    pub header: std_msgs::Header,
+    /// Stamp of the sample that started the interval.
+    pub start: builtin_interfaces::Time,
+    /// Stamp of the last sample of the interval.
+    pub end: builtin_interfaces::Time,
+    /// Rotation of the body over the interval as a rotation vector in rad,
+    /// `q_end = q_start ⊗ exp(delta_angle)`, with coning compensation.
+    pub delta_angle: geometry_msgs::Vector3,
+    /// Specific force velocity increment in m/s, expressed in the body frame
+    /// at the start of the interval, with rotation and sculling
+    /// compensation. Gravity is not removed.
+    pub delta_velocity: geometry_msgs::Vector3,
+    /// Number of samples integrated over the interval.
+    pub sample_count: u32,
+}
+
+/// `edgefirst_imu/msg/EgoRotation`: sensor rotation over a requested
+/// interval, the reply to a rotation query.
+#[derive(Serialize, Deserialize, PartialEq, Clone, Debug)]
+pub struct EgoRotation {
+    /// Stamp of the end of the interval and the IMU frame ID.
+    pub header: std_msgs::Header,
+    /// Requested start of the interval.
+    pub start: builtin_interfaces::Time,
+    /// Requested end of the interval.
+    pub end: builtin_interfaces::Time,
+    /// Rotation of the body at the end relative to the body at the start,
+    /// `q_end = q_start ⊗ rotation`.
+    pub rotation: geometry_msgs::Quaternion,
+    /// Mean angular velocity over the interval in rad/s.
+    pub angular_velocity: geometry_msgs::Vector3,
+    /// Number of samples, including partial ones, in the interval.
+    pub sample_count: u32,
+}
+
+/// `edgefirst_imu/msg/PreintegratedImu`: IMU samples preintegrated between
+/// two camera frames, with the Jacobians needed to correct the result for a
+/// change of bias without reintegrating.
+///
+/// The increments follow Forster et al., "On-Manifold Preintegration for
+/// Real-Time Visual-Inertial Odometry": gravity is not included and is
+/// applied by the consumer. Jacobians are row-major 3x3 matrices.
+#[derive(Serialize, Deserialize, PartialEq, Clone, Debug)]
+pub struct PreintegratedImu {
+    /// Stamp of the end frame and the IMU frame ID.
+    pub header: std_msgs::Header,
+    /// Stamp of the frame starting the interval.
+    pub start: builtin_interfaces::Time,
+    /// Stamp of the frame ending the interval.
+    pub end: builtin_interfaces::Time,
+    /// Rotation of the body from the start to the end frame.
+    pub delta_rotation: geometry_msgs::Quaternion,
+    /// Velocity increment in m/s in the body frame at the start.
+    pub delta_velocity: geometry_msgs::Vector3,
+    /// Position increment in m in the body frame at the start.
+    pub delta_position: geometry_msgs::Vector3,
+    /// Gyroscope bias in rad/s the increments were computed with.
+    pub gyro_bias: geometry_msgs::Vector3,
+    /// Accelerometer bias in m/s² the increments were computed with.
+    pub accel_bias: geometry_msgs::Vector3,
+    pub d_rotation_d_gyro_bias: [f64; 9],
+    pub d_velocity_d_gyro_bias: [f64; 9],
+    pub d_velocity_d_accel_bias: [f64; 9],
+    pub d_position_d_gyro_bias: [f64; 9],
+    pub d_position_d_accel_bias: [f64; 9],
+    /// Number of samples, including partial ones, in the interval.
+    pub sample_count: u32,
+}
+
+/// `edgefirst_imu/msg/ImuBatch`: consecutive IMU samples packed into one
+/// message to reduce the per-sample publish overhead.
+///
+/// ```text
+/// std_msgs/Header header      # stamp of the first sample, IMU frame ID
+/// ImuBatchSample[] samples    # in time order
+/// ```
+#[derive(Serialize, Deserialize, PartialEq, Clone, Debug)]
+pub struct ImuBatch {
+    pub header: std_msgs::Header,
+    pub samples: Vec<ImuBatchSample>,
+}
+
+/// One sample of an [`ImuBatch`], carrying the `sensor_msgs/Imu` fields that
+/// change between samples.
+///
+/// ```text
+/// builtin_interfaces/Time stamp
+/// geometry_msgs/Quaternion orientation
+/// geometry_msgs/Vector3 angular_velocity      # rad/s
+/// geometry_msgs/Vector3 linear_acceleration   # m/s^2
+/// ```
+#[derive(Serialize, Deserialize, PartialEq, Clone, Debug)]
+pub struct ImuBatchSample {
+    pub stamp: builtin_interfaces::Time,
+    pub orientation: geometry_msgs::Quaternion,
+    pub angular_velocity: geometry_msgs::Vector3,
+    pub linear_acceleration: geometry_msgs::Vector3,
+}
+
+impl ImuBatch {
+    /// Decodes the CDR payload of a message received on the batch topic.
+    pub fn decode(payload: &[u8]) -> Result<Self, serde_cdr::Error> {
+        serde_cdr::deserialize(payload)
+    }
+
+    /// Expands the batch into one `sensor_msgs/Imu` per sample, as they
+    /// would have been published individually.
+    pub fn messages(&self) -> impl Iterator<Item = sensor_msgs::IMU> + '_ {
+        self.samples.iter().map(|sample| sensor_msgs::IMU {
+            header: std_msgs::Header {
+                stamp: sample.stamp.clone(),
+                frame_id: self.header.frame_id.clone(),
+            },
+            orientation: sample.orientation,
+            orientation_covariance: UNKNOWN_COVARIANCE,
+            angular_velocity: sample.angular_velocity,
+            angular_velocity_covariance: UNKNOWN_COVARIANCE,
+            linear_acceleration: sample.linear_acceleration,
+            linear_acceleration_covariance: UNKNOWN_COVARIANCE,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_batch_round_trip() {
+        let vector = geometry_msgs::Vector3 {
+            x: 0.1,
+            y: -0.2,
+            z: 9.8,
+        };
+        let batch = ImuBatch {
+            header: std_msgs::Header {
+                stamp: builtin_interfaces::Time::new(1, 0),
+                frame_id: "imu_link".to_owned(),
+            },
+            samples: (0..3)
+                .map(|i| ImuBatchSample {
+                    stamp: builtin_interfaces::Time::new(1, i * 5_000_000),
+                    orientation: geometry_msgs::Quaternion {
+                        x: 0.0,
+                        y: 0.0,
+                        z: 0.0,
+                        w: 1.0,
+                    },
+                    angular_velocity: vector,
+                    linear_acceleration: vector,
+                })
+                .collect(),
+        };
+
+        let payload = serde_cdr::serialize(&batch).unwrap();
+        let decoded = ImuBatch::decode(&payload).unwrap();
+        assert_eq!(decoded, batch);
+        let messages: Vec<_> = decoded.messages().collect();
+        assert_eq!(messages.len(), 3);
+        assert_eq!(messages[2].header.stamp.nanosec, 10_000_000);
+        assert_eq!(messages[2].header.frame_id, "imu_link");
+    }
+
+    #[test]
+    fn test_covariance_has_no_length_prefix() {
+        let mut covariance = Covariance::default();
+        covariance.0[35] = 2.0;
+        let twist = TwistWithCovariance {
+            twist: geometry_msgs::Twist {
+                linear: geometry_msgs::Vector3 {
+                    x: 1.0,
+                    y: 0.0,
+                    z: 0.0,
+                },
+                angular: geometry_msgs::Vector3 {
+                    x: 0.0,
+                    y: 0.0,
+                    z: 0.0,
+                },
+            },
+            covariance,
+        };
+        let payload = serde_cdr::serialize(&twist).unwrap();
+        // Encapsulation header followed by 6 + 36 doubles.
+        assert_eq!(payload.len(), 4 + 42 * 8);
+        let decoded: TwistWithCovariance = serde_cdr::deserialize(&payload).unwrap();
+        assert_eq!(decoded, twist);
+    }
+}
@@ -0,0 +1,233 @@
+// Copyright 2025 Au-Zone Technologies Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Short-term strapdown dead reckoning.
+//!
+//! The acceleration is rotated into the world frame by the orientation,
+//! gravity is removed and the result is integrated twice into velocity and
+//! position. Any error in the acceleration grows the position error with
+//! the square of time, so the integration is only trusted for a few
+//! seconds: zero velocity updates reset the velocity whenever the body is
+//! at rest, and without one for longer than the horizon the output stops
+//! until the body is at rest again.
+//!
+//! The world frame specific force at rest is tracked during zero velocity
+//! updates, which absorbs the accelerometer bias and the tilt error at the
+//! current attitude instead of assuming exactly 1 g along the vertical.
+use crate::{
+    args::Args,
+    convention::Convention,
+    math::Vec3,
+    msgs::{Covariance, Odometry, PoseWithCovariance, TwistWithCovariance},
+    sample::{quaternion, vector3, Sample},
+    stationary::StationaryDetector,
+};
+use edgefirst_schemas::{geometry_msgs, std_msgs};
+use log::{info, warn};
+use std::time::Instant;
+
+/// Standard gravity in m/s².
+const GRAVITY: f64 = 9.80665;
+
+/// World acceleration in m/s² above which the body is not at rest, even if
+/// the acceleration is steady.
+const ZUPT_ACCEL: f64 = 0.5;
+
+/// Velocity variance in m²/s² after a zero velocity update.
+const ZUPT_VARIANCE: f64 = 1e-4;
+
+/// Time constant of the specific force at rest average in seconds.
+const GRAVITY_TIME_CONSTANT: f64 = 2.0;
+
+/// Angular velocity variance in rad²/s², the order of the gyroscope noise.
+const GYRO_VARIANCE: f64 = 1e-4;
+
+pub struct DeadReckoning {
+    odom_frame: String,
+    frame_id: String,
+    accel_noise: f64,
+    /// Horizon and publish period in nanoseconds.
+    horizon: u64,
+    period: u64,
+    detector: StationaryDetector,
+
+    /// World frame specific force at rest.
+    gravity: Vec3,
+    position: Vec3,
+    velocity: Vec3,
+    /// Position variance, position-velocity covariance and velocity
+    /// variance, equal on every axis.
+    variance: [f64; 3],
+    last_stamp: Option<u64>,
+    last_zupt: Option<u64>,
+    last_publish: Option<u64>,
+    expired: bool,
+}
+
+impl DeadReckoning {
+    pub fn new(args: &Args) -> Self {
+        let up = match args.convention {
+            Convention::Enu => GRAVITY,
+            Convention::Ned => -GRAVITY,
+        };
+        Self {
+            odom_frame: args.odom_frame.clone(),
+            frame_id: args.frame_id.clone(),
+            accel_noise: args.odom_accel_noise,
+            horizon: (args.odom_horizon * 1e9) as u64,
+            period: if args.rate > 0.0 {
+                (1e9 / args.rate).round() as u64
+            } else {
+                0
+            },
+            detector: StationaryDetector::new(args),
+            gravity: Vec3::new(0.0, 0.0, up),
+            position: Vec3::default(),
+            velocity: Vec3::default(),
+            variance: [0.0, 0.0, ZUPT_VARIANCE],
+            last_stamp: None,
+            last_zupt: None,
+            last_publish: None,
+            expired: false,
+        }
+    }
+
+    /// Integrates an output frame sample and returns the odometry when it
+    /// is due at the --rate of the IMU output.
+    pub fn update(&mut self, sample: &Sample) -> Option<Odometry> {
+        self.step(Instant::now(), sample)
+    }
+
+    fn step(&mut self, now: Instant, sample: &Sample) -> Option<Odometry> {
+        let stamp = sample.stamp.to_nanos();
+        let dt = self
+            .last_stamp
+            .replace(stamp)
+            .map_or(0.0, |t| stamp.saturating_sub(t) as f64 * 1e-9);
+        self.detector.step(now, sample);
+        let world = sample.orientation.rotate(sample.linear_acceleration);
+
+        if self.detector.is_stationary() && (world - self.gravity).norm() < ZUPT_ACCEL {
+            if self.last_zupt.is_none() || self.expired {
+                info!("Dead reckoning started at rest");
+            }
+            self.expired = false;
+            self.last_zupt = Some(stamp);
+            self.velocity = Vec3::default();
+            self.variance = [self.variance[0], 0.0, ZUPT_VARIANCE];
+            let alpha = (dt / GRAVITY_TIME_CONSTANT).min(1.0);
+            self.gravity = self.gravity + (world - self.gravity).scale(alpha);
+        } else if let Some(zupt) = self.last_zupt.filter(|_| !self.expired) {
+            if stamp.saturating_sub(zupt) > self.horizon {
+                warn!(
+                    "No zero velocity update for {} s, dead reckoning suspended",
+                    self.horizon as f64 * 1e-9
+                );
+                self.expired = true;
+            } else {
+                let acceleration = world - self.gravity;
+                self.position =
+                    self.position + self.velocity.scale(dt) + acceleration.scale(0.5 * dt * dt);
+                self.velocity = self.velocity + acceleration.scale(dt);
+
+                let q = self.accel_noise * self.accel_noise;
+                let [pp, pv, vv] = self.variance;
+                self.variance = [
+                    pp + 2.0 * dt * pv + dt * dt * vv + q * dt.powi(3) / 3.0,
+                    pv + dt * vv + q * dt * dt / 2.0,
+                    vv + q * dt,
+                ];
+            }
+        }
+
+        if self.last_zupt.is_none() || self.expired {
+            return None;
+        }
+        if self
+            .last_publish
+            .is_some_and(|t| stamp.saturating_sub(t) < self.period)
+        {
+            return None;
+        }
+        self.last_publish = Some(stamp);
+
+        let orientation = sample.heading_accuracy * sample.heading_accuracy;
+        Some(Odometry {
+            header: std_msgs::Header {
+                stamp: sample.stamp.clone(),
+                frame_id: self.odom_frame.clone(),
+            },
+            child_frame_id: self.frame_id.clone(),
+            pose: PoseWithCovariance {
+                pose: geometry_msgs::Pose {
+                    position: geometry_msgs::Point {
+                        x: self.position.x,
+                        y: self.position.y,
+                        z: self.position.z,
+                    },
+                    orientation: quaternion(sample.orientation),
+                },
+                covariance: diagonal([self.variance[0], orientation]),
+            },
+            twist: TwistWithCovariance {
+                twist: geometry_msgs::Twist {
+                    linear: vector3(sample.orientation.conjugate().rotate(self.velocity)),
+                    angular: vector3(sample.angular_velocity),
+                },
+                covariance: diagonal([self.variance[2], GYRO_VARIANCE]),
+            },
+        })
+    }
+}
+
+/// Covariance with the linear and the angular variance on the diagonal.
+fn diagonal([linear, angular]: [f64; 2]) -> Covariance {
+    let mut covariance = Covariance::default();
+    for i in 0..6 {
+        covariance.0[i * 7] = if i < 3 { linear } else { angular };
+    }
+    covariance
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::math::Quat;
+    use clap::Parser;
+    use edgefirst_schemas::builtin_interfaces;
+    use std::time::Duration;
+
+    #[test]
+    fn test_integrates_from_rest() {
+        let args = Args::parse_from(["edgefirst-imu", "--odom"]);
+        let mut odometry = DeadReckoning::new(&args);
+        let mut sample = Sample {
+            stamp: builtin_interfaces::Time::new(0, 0),
+            orientation: Quat::new(1.0, 0.0, 0.0, 0.0),
+            angular_velocity: Vec3::default(),
+            linear_acceleration: Vec3::new(0.0, 0.0, GRAVITY),
+            heading_accuracy: 0.0,
+        };
+
+        let start = Instant::now();
+        let mut msg = None;
+        for i in 0..400 {
+            // One second at rest, then one second at 1 m/s² along X.
+            if i == 200 {
+                sample.linear_acceleration.x = 1.0;
+            }
+            sample.stamp = builtin_interfaces::Time::from_nanos(1_000_000_000 + i * 5_000_000);
+            msg = odometry.step(start + Duration::from_millis(5 * i), &sample);
+            if i == 199 {
+                assert_eq!(msg.as_ref().unwrap().pose.pose.position.x, 0.0);
+            }
+        }
+
+        let msg = msg.unwrap();
+        assert!((msg.twist.twist.linear.x - 1.0).abs() < 1e-9);
+        assert!((msg.pose.pose.position.x - 0.5).abs() < 1e-9);
+        // Velocity variance after a second of q = 0.01 m²/s³.
+        assert!((msg.twist.covariance.0[0] - ZUPT_VARIANCE - 0.01).abs() < 1e-9);
+        assert!(msg.pose.covariance.0[7] > 0.0);
+    }
+}
@@ -0,0 +1,393 @@
+// Copyright 2025 Au-Zone Technologies Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+//! IMU preintegration between camera frames.
+//!
+//! Frame stamps arrive from a subscription on any stamped topic, such as the
+//! camera info, and the buffered IMU samples between consecutive frames are
+//! integrated into one rotation, velocity and position increment following
+//! Forster et al., "On-Manifold Preintegration for Real-Time
+//! Visual-Inertial Odometry" (TRO 2017). Each sample holds its value over
+//! the time since the previous sample, and samples straddling a frame are
+//! split at the frame stamp. An interval the buffered samples do not fully
+//! cover, after a gap in the samples or dropped frames, is not published.
+use crate::{
+    args::Args,
+    math::{Quat, Vec3},
+    msgs::PreintegratedImu,
+    sample::{quaternion, vector3, Sample},
+};
+use edgefirst_schemas::{builtin_interfaces, serde_cdr, std_msgs};
+use log::warn;
+use std::collections::VecDeque;
+
+/// Samples older than this, in nanoseconds, are dropped when no frame
+/// needs them.
+const MAX_BUFFER: u64 = 5_000_000_000;
+
+/// Frames waiting for samples beyond this count are dropped oldest first,
+/// so frames arriving without IMU samples do not accumulate.
+const MAX_FRAMES: usize = 100;
+
+type Mat3 = [[f64; 3]; 3];
+
+/// Reads the stamp of any message that starts with a `std_msgs/Header`.
+pub fn frame_stamp(payload: &[u8]) -> Result<builtin_interfaces::Time, String> {
+    serde_cdr::deserialize::<std_msgs::Header>(payload)
+        .map(|header| header.stamp)
+        .map_err(|e| e.to_string())
+}
+
+pub struct Preintegrator {
+    frame_id: String,
+    /// `(stamp, angular velocity, linear acceleration)` in time order.
+    samples: VecDeque<(u64, Vec3, Vec3)>,
+    frames: VecDeque<builtin_interfaces::Time>,
+    last_frame: Option<builtin_interfaces::Time>,
+}
+
+impl Preintegrator {
+    pub fn new(args: &Args) -> Self {
+        Self {
+            frame_id: args.frame_id.clone(),
+            samples: VecDeque::new(),
+            frames: VecDeque::new(),
+            last_frame: None,
+        }
+    }
+
+    pub fn push_sample(&mut self, sample: &Sample) {
+        let stamp = sample.stamp.to_nanos();
+        if self.samples.back().is_some_and(|(t, _, _)| *t >= stamp) {
+            return;
+        }
+        self.samples
+            .push_back((stamp, sample.angular_velocity, sample.linear_acceleration));
+    }
+
+    /// Queues a camera frame stamp. Frames at or before the previous one are
+    /// ignored. When the queue is full the oldest frame is dropped and
+    /// becomes the start of the next interval.
+    pub fn push_frame(&mut self, stamp: builtin_interfaces::Time) {
+        let latest = self.frames.back().or(self.last_frame.as_ref());
+        if latest.is_some_and(|t| t.to_nanos() >= stamp.to_nanos()) {
+            return;
+        }
+        if self.frames.len() >= MAX_FRAMES {
+            self.last_frame = self.frames.pop_front();
+        }
+        self.frames.push_back(stamp);
+    }
+
+    /// Preintegrates every queued frame interval the buffered samples cover.
+    pub fn poll(&mut self) -> Vec<PreintegratedImu> {
+        let mut output = Vec::new();
+        let Some(&(latest, _, _)) = self.samples.back() else {
+            return output;
+        };
+
+        while self.frames.front().is_some_and(|t| t.to_nanos() <= latest) {
+            let Some(end) = self.frames.pop_front() else {
+                break;
+            };
+            if let Some(start) = self.last_frame.replace(end.clone()) {
+                match self.integrate(start, end.clone()) {
+                    Ok(preintegrated) => output.push(preintegrated),
+                    Err(e) => warn!("Dropping preintegration interval: {}", e),
+                }
+            }
+            // Keep the last sample at or before the frame, which bounds the
+            // first sample of the next interval.
+            while self
+                .samples
+                .get(1)
+                .is_some_and(|(t, _, _)| *t <= end.to_nanos())
+            {
+                self.samples.pop_front();
+            }
+        }
+
+        if self.frames.is_empty() {
+            while self
+                .samples
+                .front()
+                .is_some_and(|(t, _, _)| latest - t > MAX_BUFFER)
+            {
+                self.samples.pop_front();
+            }
+        }
+        output
+    }
+
+    /// Integrates the samples from `start` to `end`. Fails unless the
+    /// buffered samples cover the whole interval.
+    fn integrate(
+        &self,
+        start: builtin_interfaces::Time,
+        end: builtin_interfaces::Time,
+    ) -> Result<PreintegratedImu, String> {
+        let (ti, tj) = (start.to_nanos(), end.to_nanos());
+        match (self.samples.front(), self.samples.back()) {
+            (Some((first, _, _)), Some((last, _, _))) if *first <= ti && tj <= *last => {}
+            (Some((first, _, _)), Some((last, _, _))) => {
+                return Err(format!(
+                    "interval {ti}..{tj} is outside the samples {first}..{last}"
+                ))
+            }
+            _ => return Err("no IMU samples".to_owned()),
+        }
+        let mut rotation = Quat::new(1.0, 0.0, 0.0, 0.0);
+        let mut velocity = Vec3::default();
+        let mut position = Vec3::default();
+        let mut dr_dbg = [[0.0; 3]; 3];
+        let mut dv_dbg = [[0.0; 3]; 3];
+        let mut dv_dba = [[0.0; 3]; 3];
+        let mut dp_dbg = [[0.0; 3]; 3];
+        let mut dp_dba = [[0.0; 3]; 3];
+        let mut count = 0;
+
+        let pairs = self.samples.iter().zip(self.samples.iter().skip(1));
+        for (&(previous, _, _), &(stamp, gyro, accel)) in pairs {
+            let (lo, hi) = (previous.max(ti), stamp.min(tj));
+            if hi <= lo {
+                continue;
+            }
+            let dt = (hi - lo) as f64 * 1e-9;
+            let dt2 = dt * dt;
+            let r = rotation.rotation_matrix();
+            let r_accel = mul(&r, &skew(accel));
+
+            // Bias Jacobians use the rotation before this step.
+            dp_dba = add(&add(&dp_dba, &scale(&dv_dba, dt)), &scale(&r, -0.5 * dt2));
+            dp_dbg = add(
+                &add(&dp_dbg, &scale(&dv_dbg, dt)),
+                &scale(&mul(&r_accel, &dr_dbg), -0.5 * dt2),
+            );
+            dv_dba = add(&dv_dba, &scale(&r, -dt));
+            dv_dbg = add(&dv_dbg, &scale(&mul(&r_accel, &dr_dbg), -dt));
+
+            let world = rotation.rotate(accel);
+            position = position + velocity.scale(dt) + world.scale(0.5 * dt2);
+            velocity = velocity + world.scale(dt);
+
+            let phi = gyro.scale(dt);
+            let increment = Quat::from_rotation_vector(phi);
+            dr_dbg = add(
+                &mul(&transpose(&increment.rotation_matrix()), &dr_dbg),
+                &scale(&right_jacobian(phi), -dt),
+            );
+            rotation = (rotation * increment).normalize();
+            count += 1;
+        }
+
+        Ok(PreintegratedImu {
+            header: std_msgs::Header {
+                stamp: end.clone(),
+                frame_id: self.frame_id.clone(),
+            },
+            start,
+            end,
+            delta_rotation: quaternion(rotation),
+            delta_velocity: vector3(velocity),
+            delta_position: vector3(position),
+            gyro_bias: vector3(Vec3::default()),
+            accel_bias: vector3(Vec3::default()),
+            d_rotation_d_gyro_bias: flatten(&dr_dbg),
+            d_velocity_d_gyro_bias: flatten(&dv_dbg),
+            d_velocity_d_accel_bias: flatten(&dv_dba),
+            d_position_d_gyro_bias: flatten(&dp_dbg),
+            d_position_d_accel_bias: flatten(&dp_dba),
+            sample_count: count,
+        })
+    }
+}
+
+fn mul(a: &Mat3, b: &Mat3) -> Mat3 {
+    std::array::from_fn(|i| std::array::from_fn(|j| (0..3).map(|k| a[i][k] * b[k][j]).sum()))
+}
+
+fn add(a: &Mat3, b: &Mat3) -> Mat3 {
+    std::array::from_fn(|i| std::array::from_fn(|j| a[i][j] + b[i][j]))
+}
+
+fn scale(a: &Mat3, s: f64) -> Mat3 {
+    a.map(|row| row.map(|v| v * s))
+}
+
+fn transpose(a: &Mat3) -> Mat3 {
+    std::array::from_fn(|i| std::array::from_fn(|j| a[j][i]))
+}
+
+fn skew(v: Vec3) -> Mat3 {
+    [[0.0, -v.z, v.y], [v.z, 0.0, -v.x], [-v.y, v.x, 0.0]]
+}
+
+fn flatten(a: &Mat3) -> [f64; 9] {
+    std::array::from_fn(|i| a[i / 3][i % 3])
+}
+
+/// Right Jacobian of SO(3) at the rotation vector `phi`.
+fn right_jacobian(phi: Vec3) -> Mat3 {
+    let identity = std::array::from_fn(|i| std::array::from_fn(|j| f64::from(i == j)));
+    let theta = phi.norm();
+    let k = skew(phi);
+    let (a, b) = if theta < 1e-6 {
+        (0.5, 1.0 / 6.0)
+    } else {
+        let t2 = theta * theta;
+        (
+            (1.0 - theta.cos()) / t2,
+            (theta - theta.sin()) / (t2 * theta),
+        )
+    };
+    add(&add(&identity, &scale(&k, -a)), &scale(&mul(&k, &k), b))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+    use edgefirst_schemas::geometry_msgs;
+
+    fn preintegrate(gyro: Vec3, accel: Vec3) -> PreintegratedImu {
+        let args = Args::parse_from(["edgefirst-imu"]);
+        let mut preintegrator = Preintegrator::new(&args);
+        let stamp = |ms: u64| builtin_interfaces::Time::from_nanos(1_000_000_000 + ms * 1_000_000);
+        // Frames at 2.5 ms and 102.5 ms split the first and last samples.
+        preintegrator.push_frame(builtin_interfaces::Time::from_nanos(1_002_500_000));
+        preintegrator.push_frame(builtin_interfaces::Time::from_nanos(1_102_500_000));
+        for i in 0..=21 {
+            preintegrator.push_sample(&Sample {
+                stamp: stamp(5 * i),
+                orientation: Quat::new(1.0, 0.0, 0.0, 0.0),
+                angular_velocity: gyro,
+                linear_acceleration: accel,
+                heading_accuracy: 0.0,
+            });
+        }
+        let mut output = preintegrator.poll();
+        assert_eq!(output.len(), 1);
+        output.remove(0)
+    }
+
+    fn mat_vec(m: &[f64; 9], v: Vec3) -> Vec3 {
+        Vec3::new(
+            m[0] * v.x + m[1] * v.y + m[2] * v.z,
+            m[3] * v.x + m[4] * v.y + m[5] * v.z,
+            m[6] * v.x + m[7] * v.y + m[8] * v.z,
+        )
+    }
+
+    fn vec(v: geometry_msgs::Vector3) -> Vec3 {
+        Vec3::new(v.x, v.y, v.z)
+    }
+
+    #[test]
+    fn test_bias_jacobians_predict_reintegration() {
+        let gyro = Vec3::new(0.3, -0.5, 1.0);
+        let accel = Vec3::new(1.0, 0.5, 9.8);
+        let nominal = preintegrate(gyro, accel);
+        let q = nominal.delta_rotation;
+        let rotation = Quat::new(q.w, q.x, q.y, q.z);
+        // Mostly yaw, 100 ms at 1 rad/s.
+        assert_eq!(nominal.start.to_nanos(), 1_002_500_000);
+        // Samples at 0 to 105 ms, the first and last are partial.
+        assert_eq!(nominal.sample_count, 21);
+        assert!((2.0 * rotation.z.atan2(rotation.w) - 0.1).abs() < 0.02);
+
+        // Reintegrating with a perturbed input is the same as the nominal
+        // input with the opposite bias.
+        let dg = Vec3::new(1e-3, -2e-3, 1e-3);
+        let da = Vec3::new(2e-2, 1e-2, -1e-2);
+        let perturbed = preintegrate(gyro + dg, accel + da);
+        let bg = Vec3::default() - dg;
+        let ba = Vec3::default() - da;
+
+        let predicted =
+            rotation * Quat::from_rotation_vector(mat_vec(&nominal.d_rotation_d_gyro_bias, bg));
+        let q = perturbed.delta_rotation;
+        let error = predicted.conjugate() * Quat::new(q.w, q.x, q.y, q.z);
+        assert!(Vec3::new(error.x, error.y, error.z).norm() < 1e-7);
+
+        let predicted = vec(nominal.delta_velocity)
+            + mat_vec(&nominal.d_velocity_d_gyro_bias, bg)
+            + mat_vec(&nominal.d_velocity_d_accel_bias, ba);
+        assert!((predicted - vec(perturbed.delta_velocity)).norm() < 1e-6);
+
+        let predicted = vec(nominal.delta_position)
+            + mat_vec(&nominal.d_position_d_gyro_bias, bg)
+            + mat_vec(&nominal.d_position_d_accel_bias, ba);
+        assert!((predicted - vec(perturbed.delta_position)).norm() < 1e-7);
+    }
+
+    #[test]
+    fn test_frame_stamp_from_stamped_message() {
+        let stamp = builtin_interfaces::Time::new(12, 345);
+        let msg = geometry_msgs::TwistStamped {
+            header: std_msgs::Header {
+                stamp: stamp.clone(),
+                frame_id: "camera".to_owned(),
+            },
+            twist: geometry_msgs::Twist {
+                linear: vector3(Vec3::new(1.0, 2.0, 3.0)),
+                angular: vector3(Vec3::default()),
+            },
+        };
+        let payload = serde_cdr::serialize(&msg).unwrap();
+        assert_eq!(frame_stamp(&payload).unwrap(), stamp);
+    }
+
+    #[test]
+    fn test_gap_drops_interval() {
+        let args = Args::parse_from(["edgefirst-imu"]);
+        let mut preintegrator = Preintegrator::new(&args);
+        let push_samples = |preintegrator: &mut Preintegrator, from: u64, to: u64| {
+            for ms in (from..=to).step_by(5) {
+                preintegrator.push_sample(&Sample {
+                    stamp: builtin_interfaces::Time::from_nanos(ms * 1_000_000),
+                    orientation: Quat::new(1.0, 0.0, 0.0, 0.0),
+                    angular_velocity: Vec3::new(0.0, 0.0, 1.0),
+                    linear_acceleration: Vec3::new(0.0, 0.0, 9.8),
+                    heading_accuracy: 0.0,
+                });
+            }
+        };
+        let frame = |ms: u64| builtin_interfaces::Time::from_nanos(ms * 1_000_000);
+
+        // A frame stamped before the first sample starts no interval.
+        preintegrator.push_frame(frame(500));
+        push_samples(&mut preintegrator, 1_000, 1_100);
+        assert!(preintegrator.poll().is_empty());
+        preintegrator.push_frame(frame(1_050));
+        assert!(preintegrator.poll().is_empty());
+
+        // No frame for longer than the buffer, the samples at the start of
+        // the next interval are dropped.
+        push_samples(&mut preintegrator, 1_105, 8_000);
+        assert!(preintegrator.poll().is_empty());
+        preintegrator.push_frame(frame(7_900));
+        assert!(preintegrator.poll().is_empty());
+
+        // The following interval is covered again.
+        preintegrator.push_frame(frame(7_950));
+        let output = preintegrator.poll();
+        assert_eq!(output.len(), 1);
+        assert_eq!(output[0].start, frame(7_900));
+        assert_eq!(output[0].sample_count, 10);
+    }
+
+    #[test]
+    fn test_frame_queue_is_bounded() {
+        let args = Args::parse_from(["edgefirst-imu"]);
+        let mut preintegrator = Preintegrator::new(&args);
+        // Frames at 30 Hz for 10 s without any IMU sample.
+        for i in 1..=300 {
+            preintegrator.push_frame(builtin_interfaces::Time::from_nanos(i * 33_333_333));
+        }
+        assert_eq!(preintegrator.frames.len(), MAX_FRAMES);
+        assert_eq!(
+            preintegrator.last_frame.as_ref().map(|t| t.to_nanos()),
+            Some(200 * 33_333_333)
+        );
+    }
+}
@@ -0,0 +1,66 @@
+// Copyright 2025 Au-Zone Technologies Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+//! IMU sample passed through the host-side processing stages.
+use crate::{
+    math::{Quat, Vec3},
+    msgs::{ImuBatchSample, UNKNOWN_COVARIANCE},
+};
+use edgefirst_schemas::{builtin_interfaces, geometry_msgs, sensor_msgs, std_msgs};
+
+/// One IMU reading: orientation of the body in the reference frame, with
+/// angular velocity and linear acceleration expressed in the body frame.
+#[derive(Debug, Clone)]
+pub struct Sample {
+    pub stamp: builtin_interfaces::Time,
+    pub orientation: Quat,
+    pub angular_velocity: Vec3,
+    pub linear_acceleration: Vec3,
+    /// Heading accuracy estimate of the orientation in radians.
+    pub heading_accuracy: f64,
+}
+
+impl Sample {
+    /// Builds the `sensor_msgs/Imu` message for this sample.
+    pub fn to_imu(&self, frame_id: &str) -> sensor_msgs::IMU {
+        sensor_msgs::IMU {
+            header: std_msgs::Header {
+                stamp: self.stamp.clone(),
+                frame_id: frame_id.to_owned(),
+            },
+            orientation: quaternion(self.orientation),
+            orientation_covariance: UNKNOWN_COVARIANCE,
+            angular_velocity: vector3(self.angular_velocity),
+            angular_velocity_covariance: UNKNOWN_COVARIANCE,
+            linear_acceleration: vector3(self.linear_acceleration),
+            linear_acceleration_covariance: UNKNOWN_COVARIANCE,
+        }
+    }
+
+    /// Builds the entry of this sample in an `ImuBatch`.
+    pub fn to_batch_sample(&self) -> ImuBatchSample {
+        ImuBatchSample {
+            stamp: self.stamp.clone(),
+            orientation: quaternion(self.orientation),
+            angular_velocity: vector3(self.angular_velocity),
+            linear_acceleration: vector3(self.linear_acceleration),
+        }
+    }
+}
+
+pub fn quaternion(q: Quat) -> geometry_msgs::Quaternion {
+    geometry_msgs::Quaternion {
+        x: q.x,
+        y: q.y,
+        z: q.z,
+        w: q.w,
+    }
+}
+
+pub fn vector3(v: Vec3) -> geometry_msgs::Vector3 {
+    geometry_msgs::Vector3 {
+        x: v.x,
+        y: v.y,
+        z: v.z,
+    }
+}
@@ -0,0 +1,318 @@
+// Copyright 2025 Au-Zone Technologies Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Raw SH-2 requests not implemented by `bno08x-rs`.
+//!
+//! The driver handles the sensor reports and the sensor orientation write
+//! but keeps its packet functions private. The interface is taken from an
+//! initialized driver with `BNO08x::free`, the requests are sent as SHTP
+//! packets on the sensor hub control channel, and the interface is handed
+//! back to a new driver without resetting the sensor, so the enabled
+//! reports keep streaming. The new driver has no callbacks and has not
+//! received any report yet.
+//!
+//! Responses are read from the hub control channel and matched by report
+//! ID; the sensor reports received in between are dropped.
+use crate::driver::Driver;
+use bno08x_rs::{
+    interface::{
+        gpio::{GpiodIn, GpiodOut},
+        spidev::SpiDevice,
+        SensorInterface, SpiInterface,
+    },
+    BNO08x,
+};
+use log::trace;
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+type Interface = SpiInterface<SpiDevice, GpiodIn, GpiodOut>;
+
+const HEADER_LENGTH: usize = 4;
+const RECEIVE_LENGTH: usize = 2048;
+
+/// SHTP channel of the sensor hub control reports.
+const CHANNEL_HUB_CONTROL: u8 = 2;
+
+/// Longest wait for the polling read of a packet, in milliseconds.
+const POLL_MS: usize = 50;
+
+/// Longest wait for a response. FRS writes wait for the flash.
+const RESPONSE_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Attempts of an FRS request while the sensor reports busy.
+const FRS_TRIES: usize = 5;
+
+const COMMAND_REQUEST: u8 = 0xF2;
+const FRS_READ_RESPONSE: u8 = 0xF3;
+const FRS_READ_REQUEST: u8 = 0xF4;
+const FRS_WRITE_RESPONSE: u8 = 0xF5;
+const FRS_WRITE_DATA: u8 = 0xF6;
+const FRS_WRITE_REQUEST: u8 = 0xF7;
+
+/// Length of the hub control reports, which can share a packet.
+fn report_length(report_id: u8) -> Option<usize> {
+    match report_id {
+        0xF1 | FRS_READ_RESPONSE => Some(16),
+        FRS_WRITE_RESPONSE => Some(4),
+        0xFC => Some(17),
+        _ => None,
+    }
+}
+
+const COMMAND_TARE: u8 = 0x03;
+
+const TARE_NOW: u8 = 0;
+const TARE_PERSIST: u8 = 1;
+const TARE_SET_REORIENTATION: u8 = 2;
+
+/// Axes of a tare, as a bitmap of X, Y and Z.
+pub const TARE_ALL_AXES: u8 = 0x07;
+pub const TARE_Z_AXIS: u8 = 0x04;
+
+/// Tare basis: the rotation vector.
+const TARE_ROTATION_VECTOR: u8 = 0;
+
+pub struct Sh2 {
+    interface: Interface,
+    /// Sequence number of the next packet on each channel.
+    sequence: [u8; 6],
+    /// Sequence number of the next command request.
+    command_sequence: u8,
+    /// Hub control reports received and not yet handled.
+    received: VecDeque<Vec<u8>>,
+    buf: Vec<u8>,
+}
+
+impl Sh2 {
+    /// Takes over the interface of an initialized driver.
+    pub fn new(driver: Driver) -> Self {
+        Self {
+            interface: driver.imu_driver.free(),
+            sequence: [0; 6],
+            command_sequence: 0,
+            received: VecDeque::new(),
+            buf: vec![0; RECEIVE_LENGTH],
+        }
+    }
+
+    /// Hands the interface back to a driver, keeping the sensor state.
+    pub fn into_driver<'a>(self) -> Driver<'a> {
+        Driver {
+            imu_driver: BNO08x::new_with_interface(self.interface),
+        }
+    }
+
+    /// Zeroes the current orientation on the given axes.
+    pub fn tare_now(&mut self, axes: u8) -> Result<(), String> {
+        self.command(
+            COMMAND_TARE,
+            [TARE_NOW, axes, TARE_ROTATION_VECTOR, 0, 0, 0, 0, 0, 0],
+        )
+    }
+
+    /// Saves the current tare in the system orientation FRS record.
+    pub fn persist_tare(&mut self) -> Result<(), String> {
+        self.command(COMMAND_TARE, [TARE_PERSIST, 0, 0, 0, 0, 0, 0, 0, 0])
+    }
+
+    /// Removes the current tare. A zero reorientation clears it.
+    pub fn clear_tare(&mut self) -> Result<(), String> {
+        self.command(
+            COMMAND_TARE,
+            [TARE_SET_REORIENTATION, 0, 0, 0, 0, 0, 0, 0, 0],
+        )
+    }
+
+    /// Reads an FRS record as 32-bit words, or `None` if it is empty.
+    pub fn read_frs(&mut self, record: u16) -> Result<Option<Vec<u32>>, String> {
+        for _ in 0..FRS_TRIES {
+            let [type_lsb, type_msb] = record.to_le_bytes();
+            self.received.clear();
+            self.send(
+                CHANNEL_HUB_CONTROL,
+                &[FRS_READ_REQUEST, 0, 0, 0, type_lsb, type_msb, 0, 0],
+            )?;
+            let mut words = Vec::new();
+            loop {
+                let response = self.receive(FRS_READ_RESPONSE)?;
+                if u16::from_le_bytes([response[12], response[13]]) != record {
+                    continue;
+                }
+                let length = (response[1] >> 4) as usize;
+                let offset = u16::from_le_bytes([response[2], response[3]]) as usize;
+                for (i, word) in response[4..12].chunks(4).take(length).enumerate() {
+                    if words.len() <= offset + i {
+                        words.resize(offset + i + 1, 0);
+                    }
+                    words[offset + i] = u32::from_le_bytes(word.try_into().unwrap());
+                }
+                match response[1] & 0x0F {
+                    0 | 6 => {}
+                    3 | 7 => return Ok((!words.is_empty()).then_some(words)),
+                    5 => return Ok(None),
+                    2 => break,
+                    1 => return Err(format!("unrecognized FRS record {record:#06X}")),
+                    4 => return Err(format!("FRS record {record:#06X} offset out of range")),
+                    status => {
+                        return Err(format!(
+                            "FRS record {record:#06X} read failed with status {status}"
+                        ))
+                    }
+                }
+            }
+            trace!("Sensor busy reading FRS record {:#06X}", record);
+            std::thread::sleep(Duration::from_millis(100));
+        }
+        Err(format!(
+            "the sensor stayed busy reading FRS record {record:#06X}"
+        ))
+    }
+
+    /// Writes an FRS record. An empty record erases it.
+    pub fn write_frs(&mut self, record: u16, words: &[u32]) -> Result<(), String> {
+        for _ in 0..FRS_TRIES {
+            let [length_lsb, length_msb] = (words.len() as u16).to_le_bytes();
+            let [type_lsb, type_msb] = record.to_le_bytes();
+            self.received.clear();
+            self.send(
+                CHANNEL_HUB_CONTROL,
+                &[
+                    FRS_WRITE_REQUEST,
+                    0,
+                    length_lsb,
+                    length_msb,
+                    type_lsb,
+                    type_msb,
+                ],
+            )?;
+            match self.write_status(record)? {
+                // Busy
+                2 => {
+                    trace!("Sensor busy writing FRS record {:#06X}", record);
+                    std::thread::sleep(Duration::from_millis(100));
+                    continue;
+                }
+                // Write complete, for an erase.
+                3 if words.is_empty() => return Ok(()),
+                // Write mode entered
+                4 if !words.is_empty() => {}
+                status => {
+                    return Err(format!(
+                        "FRS record {record:#06X} write not started, status {status}"
+                    ))
+                }
+            }
+            for (i, pair) in words.chunks(2).enumerate() {
+                let [offset_lsb, offset_msb] = ((2 * i) as u16).to_le_bytes();
+                let mut body = vec![FRS_WRITE_DATA, 0, offset_lsb, offset_msb];
+                body.extend(pair[0].to_le_bytes());
+                body.extend(pair.get(1).copied().unwrap_or(0).to_le_bytes());
+                self.send(CHANNEL_HUB_CONTROL, &body)?;
+            }
+            // One word received response per data request, then the record
+            // is validated and written.
+            loop {
+                match self.write_status(record)? {
+                    0 | 8 => {}
+                    3 => return Ok(()),
+                    status => {
+                        return Err(format!(
+                            "FRS record {record:#06X} write failed with status {status}"
+                        ))
+                    }
+                }
+            }
+        }
+        Err(format!(
+            "the sensor stayed busy writing FRS record {record:#06X}"
+        ))
+    }
+
+    /// Waits for the status of an FRS write, failing on the error
+    /// statuses.
+    fn write_status(&mut self, record: u16) -> Result<u8, String> {
+        let response = self.receive(FRS_WRITE_RESPONSE)?;
+        match response[1] {
+            1 => Err(format!("unrecognized FRS record {record:#06X}")),
+            5 => Err(format!("FRS record {record:#06X} write failed")),
+            6 => Err(format!("FRS record {record:#06X} not in write mode")),
+            7 => Err(format!("invalid length for FRS record {record:#06X}")),
+            9 => Err(format!("invalid data for FRS record {record:#06X}")),
+            10 => Err(format!("device error writing FRS record {record:#06X}")),
+            11 => Err(format!("FRS record {record:#06X} is read-only")),
+            status => Ok(status),
+        }
+    }
+
+    /// Waits for a hub control report, dropping the reports with another
+    /// ID.
+    fn receive(&mut self, report_id: u8) -> Result<Vec<u8>, String> {
+        let deadline = Instant::now() + RESPONSE_TIMEOUT;
+        loop {
+            while let Some(report) = self.received.pop_front() {
+                if report[0] == report_id {
+                    return Ok(report);
+                }
+                trace!("Dropped report {:#04X}", report[0]);
+            }
+            if Instant::now() > deadline {
+                return Err(format!(
+                    "the sensor did not send report {report_id:#04X} in {} ms",
+                    RESPONSE_TIMEOUT.as_millis()
+                ));
+            }
+            let received = self
+                .interface
+                .read_with_timeout(&mut self.buf, POLL_MS)
+                .map_err(|e| format!("could not read from the sensor: {e:?}"))?;
+            self.queue(received);
+        }
+    }
+
+    /// Queues the hub control reports of the packet in the buffer.
+    fn queue(&mut self, length: usize) {
+        if length <= HEADER_LENGTH {
+            return;
+        }
+        let length = length.min(self.buf.len());
+        if self.buf[2] != CHANNEL_HUB_CONTROL {
+            trace!("Dropped packet on channel {}", self.buf[2]);
+            return;
+        }
+        let mut cargo = &self.buf[HEADER_LENGTH..length];
+        while let Some(report_length) = cargo.first().copied().and_then(report_length) {
+            if cargo.len() < report_length {
+                break;
+            }
+            self.received.push_back(cargo[..report_length].to_vec());
+            cargo = &cargo[report_length..];
+        }
+    }
+
+    /// Sends a command request. The tare commands have no response.
+    fn command(&mut self, command: u8, params: [u8; 9]) -> Result<(), String> {
+        let mut body = vec![COMMAND_REQUEST, self.command_sequence, command];
+        body.extend(params);
+        self.command_sequence = self.command_sequence.wrapping_add(1);
+        self.send(CHANNEL_HUB_CONTROL, &body)
+    }
+
+    /// Sends a packet. The transfer is full duplex, so a packet the sensor
+    /// had pending is received at the same time and queued.
+    fn send(&mut self, channel: u8, body: &[u8]) -> Result<(), String> {
+        let length = body.len() + HEADER_LENGTH;
+        let sequence = &mut self.sequence[channel as usize];
+        let mut packet = vec![length as u8, (length >> 8) as u8, channel, *sequence];
+        *sequence = sequence.wrapping_add(1);
+        packet.extend_from_slice(body);
+        let received = self
+            .interface
+            .send_and_receive_packet(&packet, &mut self.buf)
+            .map_err(|e| format!("could not send to the sensor: {e:?}"))?;
+        self.queue(received);
+        Ok(())
+    }
+}
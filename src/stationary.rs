@@ -0,0 +1,182 @@
+// Copyright 2025 Au-Zone Technologies Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Zero-velocity detection.
+//!
+//! The body is considered stationary while the standard deviation of the
+//! acceleration and of the angular velocity over a sliding window stay below
+//! their thresholds and the mean angular rate is small. The BNO08x stability
+//! classifier would be a useful second input, but the driver does not parse
+//! that report, so the detection is host-side only.
+//!
+//! While stationary the mean angular velocity is the residual gyroscope bias,
+//! which is tracked with a slow moving average across stationary windows.
+use crate::{args::Args, math::Vec3, msgs::Stationary, sample::vector3, sample::Sample};
+use edgefirst_schemas::{builtin_interfaces, std_msgs};
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+/// Mean angular rate in rad/s above which the body is turning, even if the
+/// rate is steady.
+const MAX_RATE: f64 = 0.05;
+
+/// Time constant of the gyroscope bias average in seconds.
+const BIAS_TIME_CONSTANT: f64 = 10.0;
+
+/// Interval at which the state is republished while it does not change.
+const STATUS_INTERVAL: Duration = Duration::from_secs(1);
+
+pub struct StationaryDetector {
+    frame_id: String,
+    window: Duration,
+    accel_std: f64,
+    gyro_std: f64,
+
+    samples: VecDeque<(Instant, Vec3, Vec3)>,
+    stationary: bool,
+    since: builtin_interfaces::Time,
+    bias: Option<Vec3>,
+    last_update: Option<Instant>,
+    last_status: Option<Instant>,
+}
+
+impl StationaryDetector {
+    pub fn new(args: &Args) -> Self {
+        Self {
+            frame_id: args.frame_id.clone(),
+            window: Duration::from_secs_f64(args.stationary_window),
+            accel_std: args.stationary_accel_std,
+            gyro_std: args.stationary_gyro_std,
+            samples: VecDeque::new(),
+            stationary: false,
+            since: builtin_interfaces::Time::new(0, 0),
+            bias: None,
+            last_update: None,
+            last_status: None,
+        }
+    }
+
+    /// Adds an output frame sample and returns the state when it changed or
+    /// is due for its periodic republish.
+    pub fn update(&mut self, sample: &Sample) -> Option<Stationary> {
+        self.step(Instant::now(), sample)
+    }
+
+    /// Same as [`Self::update`] with the sample arriving at `now`.
+    pub fn step(&mut self, now: Instant, sample: &Sample) -> Option<Stationary> {
+        let dt = self
+            .last_update
+            .map_or(0.0, |t| now.duration_since(t).as_secs_f64());
+        self.last_update = Some(now);
+
+        self.samples
+            .push_back((now, sample.linear_acceleration, sample.angular_velocity));
+        while self
+            .samples
+            .front()
+            .is_some_and(|(t, _, _)| now.duration_since(*t) > self.window)
+        {
+            self.samples.pop_front();
+        }
+        let full = self
+            .samples
+            .front()
+            .is_some_and(|(t, _, _)| now.duration_since(*t) >= self.window.mul_f64(0.9));
+
+        let (_, accel_std) = statistics(self.samples.iter().map(|s| s.1));
+        let (gyro_mean, gyro_std) = statistics(self.samples.iter().map(|s| s.2));
+
+        let was_stationary = self.stationary;
+        self.stationary = full
+            && accel_std < self.accel_std
+            && gyro_std < self.gyro_std
+            && gyro_mean.norm() < MAX_RATE;
+        let changed = was_stationary != self.stationary;
+        if changed {
+            self.since = sample.stamp.clone();
+        }
+
+        if self.stationary {
+            let alpha = (dt / BIAS_TIME_CONSTANT).min(1.0);
+            let bias = self.bias.get_or_insert(gyro_mean);
+            *bias = *bias + (gyro_mean - *bias).scale(alpha);
+        }
+
+        let publish = changed
+            || self
+                .last_status
+                .is_none_or(|t| now.duration_since(t) >= STATUS_INTERVAL);
+        if !publish {
+            return None;
+        }
+        self.last_status = Some(now);
+
+        Some(Stationary {
+            header: std_msgs::Header {
+                stamp: sample.stamp.clone(),
+                frame_id: self.frame_id.clone(),
+            },
+            stationary: self.stationary,
+            since: self.since.clone(),
+            accel_std,
+            gyro_std,
+            gyro_bias: vector3(self.bias.unwrap_or_default()),
+            gyro_bias_valid: self.bias.is_some(),
+        })
+    }
+
+    pub fn is_stationary(&self) -> bool {
+        self.stationary
+    }
+}
+
+/// Mean and total standard deviation (square root of the summed per-axis
+/// variances) of a set of vectors.
+fn statistics(values: impl Iterator<Item = Vec3> + Clone) -> (Vec3, f64) {
+    let n = values.clone().count().max(1) as f64;
+    let mean = values
+        .clone()
+        .fold(Vec3::default(), |acc, v| acc + v)
+        .scale(1.0 / n);
+    let variance = values.map(|v| (v - mean).dot(&(v - mean))).sum::<f64>() / n;
+    (mean, variance.sqrt())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::math::Quat;
+    use clap::Parser;
+
+    #[test]
+    fn test_detects_rest_and_bias() {
+        let args = Args::parse_from(["edgefirst-imu", "--stationary"]);
+        let mut detector = StationaryDetector::new(&args);
+        let bias = Vec3::new(0.002, -0.001, 0.003);
+        let mut sample = Sample {
+            stamp: builtin_interfaces::Time::new(0, 0),
+            orientation: Quat::new(1.0, 0.0, 0.0, 0.0),
+            angular_velocity: bias,
+            linear_acceleration: Vec3::new(0.0, 0.0, 9.81),
+            heading_accuracy: 0.0,
+        };
+
+        let start = Instant::now();
+        let mut state = None;
+        for i in 0..200 {
+            let now = start + Duration::from_millis(5 * i);
+            state = detector.step(now, &sample).or(state);
+        }
+        let state = state.unwrap();
+        assert!(state.stationary);
+        assert!(state.gyro_bias_valid);
+        assert!((state.gyro_bias.z - bias.z).abs() < 1e-9);
+
+        // A bump ends the stationary window at once.
+        sample.linear_acceleration = Vec3::new(2.0, 0.0, 9.81);
+        let state = detector.step(start + Duration::from_millis(1000), &sample);
+        assert!(!state.unwrap().stationary);
+    }
+}
@@ -0,0 +1,520 @@
+// Copyright 2025 Au-Zone Technologies Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Tare of the orientation.
+//!
+//! With the sensor backend the BNO08x tare commands are sent to the sensor,
+//! which rotates its rotation vector. It is only used when the published
+//! orientation comes from the rotation vector, not from the host-side
+//! fusion or the game rotation vector fallback. The polling loop owns the
+//! sensor interface, so commands are queued for it and the query waits for
+//! the result; a command the loop has not taken when the query times out is
+//! dropped. A tare is lost when the sensor resets unless it is persisted,
+//! which stores it in the system orientation FRS record.
+//!
+//! With the host backend the zero reference is kept on the host: a world
+//! frame rotation which brings the orientation at the time of the tare to
+//! level with zero yaw, or only to zero yaw. The published orientation is
+//! left as is and the tared one is published as a separate relative output,
+//! so the stages relying on a gravity aligned world frame are not affected.
+//! The reference can be saved to a file and is restored from it at
+//! startup.
+use crate::{
+    ahrs::FusionAlgorithm,
+    args::Args,
+    math::{Quat, Vec3},
+    msgs::TareState,
+    sample::{quaternion, Sample},
+    sh2::{Sh2, TARE_ALL_AXES, TARE_Z_AXIS},
+};
+use clap::ValueEnum;
+use edgefirst_schemas::{builtin_interfaces, serde_cdr, std_msgs};
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs,
+    io::ErrorKind,
+    path::PathBuf,
+    sync::{
+        mpsc::{self, RecvTimeoutError},
+        Mutex,
+    },
+    time::Duration,
+};
+use zenoh::Wait;
+
+/// Time to wait for the service to answer a tare command.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Time to wait for the polling loop to send a command to the sensor.
+const SENSOR_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Where the tare is applied.
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TareBackend {
+    /// BNO08x tare commands, applied by the sensor to its rotation vector.
+    #[default]
+    Sensor,
+    /// Reference kept on the host and applied to the relative output only.
+    Host,
+}
+
+/// Tare command, which is also the last chunk of its query key.
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TareAction {
+    /// Zero the current orientation on all axes.
+    All,
+    /// Zero the current heading only, keeping roll and pitch.
+    Yaw,
+    /// Save the current reference so it is restored at startup.
+    Persist,
+    /// Remove the reference and its saved copy. The sensor backend
+    /// persists the cleared tare.
+    Clear,
+}
+
+impl TareAction {
+    fn name(&self) -> &'static str {
+        match self {
+            Self::All => "all",
+            Self::Yaw => "yaw",
+            Self::Persist => "persist",
+            Self::Clear => "clear",
+        }
+    }
+}
+
+/// Checks that the sensor tare rotates the published orientation, which
+/// the host-side fusion and the game rotation vector fallback replace.
+pub fn check_backend(args: &Args) -> Result<(), String> {
+    if !args.tare || args.tare_backend != TareBackend::Sensor {
+        return Ok(());
+    }
+    if args.fusion != FusionAlgorithm::Off && args.fusion_primary {
+        return Err(
+            "the sensor tare does not apply to --fusion-primary, use --tare-backend host"
+                .to_owned(),
+        );
+    }
+    if args.mag_monitor && args.mag_fallback {
+        return Err(
+            "the sensor tare does not apply to --mag-fallback, use --tare-backend host".to_owned(),
+        );
+    }
+    Ok(())
+}
+
+#[derive(clap::Args, Debug, Clone)]
+pub struct TareArgs {
+    /// Tare command sent to the running service.
+    #[arg(value_enum)]
+    pub action: TareAction,
+}
+
+/// Reference as saved in --tare-file.
+#[derive(Serialize, Deserialize)]
+struct Saved {
+    mode: u8,
+    /// `[w, x, y, z]`.
+    offset: [f64; 4],
+}
+
+/// Sensor tare command waiting for the polling loop.
+pub struct Pending {
+    /// Identifies the command to drop on a query timeout.
+    id: u64,
+    action: TareAction,
+    done: mpsc::Sender<Result<(), String>>,
+}
+
+impl Pending {
+    /// Sends the command to the sensor and records the resulting state.
+    pub fn run(self, sh2: &mut Sh2, tare: &Mutex<Tare>) {
+        let result = match self.action {
+            TareAction::All => sh2.tare_now(TARE_ALL_AXES),
+            TareAction::Yaw => sh2.tare_now(TARE_Z_AXIS),
+            TareAction::Persist => sh2.persist_tare(),
+            // Clearing only resets the running tare, the persisted one would
+            // be applied again at the next sensor reset.
+            TareAction::Clear => sh2.clear_tare().and_then(|_| sh2.persist_tare()),
+        };
+        if result.is_ok() {
+            tare.lock().unwrap().sensor_applied(self.action);
+        }
+        // The query may have timed out.
+        let _ = self.done.send(result);
+    }
+}
+
+pub struct Tare {
+    frame_id: String,
+    backend: TareBackend,
+    pending: Vec<Pending>,
+    next_pending: u64,
+    file: PathBuf,
+    mode: u8,
+    offset: Quat,
+    persisted: bool,
+    /// Stamp and orientation of the latest output.
+    last: Option<(builtin_interfaces::Time, Quat)>,
+}
+
+impl Tare {
+    pub fn new(args: &Args) -> Self {
+        let mut tare = Self {
+            frame_id: args.frame_id.clone(),
+            backend: args.tare_backend,
+            pending: Vec::new(),
+            next_pending: 0,
+            file: args.tare_file.clone(),
+            mode: TareState::NONE,
+            offset: Quat::new(1.0, 0.0, 0.0, 0.0),
+            persisted: false,
+            last: None,
+        };
+        if tare.backend == TareBackend::Sensor {
+            return tare;
+        }
+        match fs::read_to_string(&tare.file) {
+            Ok(json) => match serde_json::from_str::<Saved>(&json) {
+                Ok(saved) => {
+                    let [w, x, y, z] = saved.offset;
+                    tare.mode = saved.mode;
+                    tare.offset = Quat::new(w, x, y, z).normalize();
+                    tare.persisted = true;
+                    info!("Restored tare reference from {}", tare.file.display());
+                }
+                Err(e) => warn!("Ignoring invalid {}: {}", tare.file.display(), e),
+            },
+            Err(e) if e.kind() == ErrorKind::NotFound => {}
+            Err(e) => warn!("Could not read {}: {}", tare.file.display(), e),
+        }
+        tare
+    }
+
+    /// Records an output frame sample and returns it with the tared
+    /// orientation.
+    pub fn apply(&mut self, sample: &Sample) -> Sample {
+        self.last = Some((sample.stamp.clone(), sample.orientation));
+        Sample {
+            orientation: self.offset * sample.orientation,
+            ..sample.clone()
+        }
+    }
+
+    /// Runs the command named by the last chunk of a query key and returns
+    /// the resulting state. Sensor commands are queued for the polling loop
+    /// and awaited, without holding the lock. A command still queued when
+    /// the wait times out is dropped, so it is never applied after the
+    /// query failed.
+    pub fn query(tare: &Mutex<Self>, action: &str) -> Result<TareState, String> {
+        let backend = tare.lock().unwrap().backend;
+        if backend == TareBackend::Host {
+            return tare.lock().unwrap().command(action);
+        }
+        let action = TareAction::from_str(action, false)
+            .map_err(|_| "expected an all, yaw, persist or clear query".to_owned())?;
+        let not_applied = || "the sensor is not running, tare not applied".to_owned();
+        let (done, result) = mpsc::channel();
+        let id = {
+            let mut tare = tare.lock().unwrap();
+            let id = tare.next_pending;
+            tare.next_pending += 1;
+            tare.pending.push(Pending { id, action, done });
+            id
+        };
+        match result.recv_timeout(SENSOR_TIMEOUT) {
+            Ok(applied) => applied?,
+            Err(RecvTimeoutError::Timeout) => {
+                let mut locked = tare.lock().unwrap();
+                let queued = locked.pending.len();
+                locked.pending.retain(|pending| pending.id != id);
+                if locked.pending.len() < queued {
+                    return Err(not_applied());
+                }
+                drop(locked);
+                // Taken by the polling loop, which is sending it.
+                result.recv().map_err(|_| not_applied())??;
+            }
+            // Dropped by a sensor reset.
+            Err(RecvTimeoutError::Disconnected) => return Err(not_applied()),
+        }
+        Ok(tare.lock().unwrap().state())
+    }
+
+    /// Takes the sensor commands waiting for the polling loop.
+    pub fn take_pending(&mut self) -> Vec<Pending> {
+        std::mem::take(&mut self.pending)
+    }
+
+    /// Records that the sensor was reset, which drops a sensor tare that
+    /// was not persisted and the commands not sent yet.
+    pub fn sensor_reset(&mut self) {
+        self.pending.clear();
+        if self.backend == TareBackend::Sensor && !self.persisted && self.mode != TareState::NONE {
+            warn!("Sensor reset, the tare was not persisted and is lost");
+            self.mode = TareState::NONE;
+        }
+    }
+
+    fn sensor_applied(&mut self, action: TareAction) {
+        match action {
+            TareAction::All => self.mode = TareState::ALL,
+            TareAction::Yaw => self.mode = TareState::YAW,
+            TareAction::Persist => self.persisted = true,
+            TareAction::Clear => self.mode = TareState::NONE,
+        }
+        if matches!(
+            action,
+            TareAction::All | TareAction::Yaw | TareAction::Clear
+        ) {
+            self.persisted = false;
+        }
+        info!("Sensor tare {}", action.name());
+    }
+
+    /// Runs a host-side command named by the last chunk of a query key and
+    /// returns the resulting reference.
+    pub fn command(&mut self, action: &str) -> Result<TareState, String> {
+        match action {
+            "all" | "yaw" => {
+                let (_, q) = self
+                    .last
+                    .clone()
+                    .ok_or("no orientation received yet to tare")?;
+                if action == "all" {
+                    self.mode = TareState::ALL;
+                    self.offset = q.conjugate();
+                } else {
+                    // Heading of the body X axis about the world vertical,
+                    // which is the yaw in either convention.
+                    let forward = q.rotate(Vec3::new(1.0, 0.0, 0.0));
+                    self.mode = TareState::YAW;
+                    self.offset = Quat::from_rpy(0.0, 0.0, -forward.y.atan2(forward.x));
+                }
+                self.persisted = false;
+                info!("Tared {}", action);
+            }
+            "persist" => {
+                let o = self.offset;
+                let saved = Saved {
+                    mode: self.mode,
+                    offset: [o.w, o.x, o.y, o.z],
+                };
+                let json = serde_json::to_string_pretty(&saved).map_err(|e| e.to_string())?;
+                if let Some(dir) = self.file.parent().filter(|d| !d.as_os_str().is_empty()) {
+                    fs::create_dir_all(dir)
+                        .map_err(|e| format!("could not create {}: {}", dir.display(), e))?;
+                }
+                fs::write(&self.file, json + "\n")
+                    .map_err(|e| format!("could not write {}: {}", self.file.display(), e))?;
+                self.persisted = true;
+                info!("Saved tare reference to {}", self.file.display());
+            }
+            "clear" => {
+                match fs::remove_file(&self.file) {
+                    Ok(()) => {}
+                    Err(e) if e.kind() == ErrorKind::NotFound => {}
+                    Err(e) => {
+                        return Err(format!("could not remove {}: {}", self.file.display(), e))
+                    }
+                }
+                self.mode = TareState::NONE;
+                self.offset = Quat::new(1.0, 0.0, 0.0, 0.0);
+                self.persisted = false;
+                info!("Tare cleared");
+            }
+            _ => return Err("expected an all, yaw, persist or clear query".to_owned()),
+        }
+        Ok(self.state())
+    }
+
+    fn state(&self) -> TareState {
+        TareState {
+            header: std_msgs::Header {
+                stamp: self
+                    .last
+                    .as_ref()
+                    .map_or(builtin_interfaces::Time::new(0, 0), |(stamp, _)| {
+                        stamp.clone()
+                    }),
+                frame_id: self.frame_id.clone(),
+            },
+            mode: self.mode,
+            offset: quaternion(self.offset),
+            persisted: self.persisted,
+        }
+    }
+}
+
+/// Sends a tare command to the running service and returns the resulting
+/// reference.
+pub fn request(args: &Args, action: TareAction) -> Result<TareState, String> {
+    let session = zenoh::open(args.clone())
+        .wait()
+        .map_err(|e| e.to_string())?;
+    let key = format!("{}/{}", args.resolve_topic(&args.tare_key), action.name());
+    let replies = session
+        .get(&key)
+        .timeout(REQUEST_TIMEOUT)
+        .wait()
+        .map_err(|e| e.to_string())?;
+    let reply = replies
+        .recv()
+        .map_err(|_| format!("no reply on {key}, is the service running with --tare?"))?;
+    match reply.result() {
+        Ok(sample) => {
+            serde_cdr::deserialize(&sample.payload().to_bytes()).map_err(|e| e.to_string())
+        }
+        Err(e) => Err(e
+            .payload()
+            .try_to_string()
+            .map_or_else(|_| "tare failed".to_owned(), |e| e.into_owned())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+
+    #[test]
+    fn test_yaw_tare_keeps_tilt() {
+        let args = Args::parse_from([
+            "edgefirst-imu",
+            "--tare",
+            "--tare-backend",
+            "host",
+            "--tare-file",
+            "/nonexistent/tare.json",
+        ]);
+        let mut tare = Tare::new(&args);
+        assert!(tare.command("yaw").is_err());
+
+        let sample = Sample {
+            stamp: builtin_interfaces::Time::new(1, 0),
+            orientation: Quat::from_rpy(0.1, -0.2, 1.2),
+            angular_velocity: Vec3::default(),
+            linear_acceleration: Vec3::default(),
+            heading_accuracy: 0.0,
+        };
+        tare.apply(&sample);
+        let state = tare.command("yaw").unwrap();
+        assert_eq!(state.mode, TareState::YAW);
+        let q = tare.apply(&sample).orientation;
+        let error = Quat::from_rpy(0.1, -0.2, 0.0).conjugate() * q;
+        assert!((error.w.abs() - 1.0).abs() < 1e-9, "{q:?}");
+
+        tare.command("all").unwrap();
+        let q = tare.apply(&sample).orientation;
+        assert!((q.w.abs() - 1.0).abs() < 1e-9, "{q:?}");
+        assert!(tare.command("tilt").is_err());
+    }
+
+    #[test]
+    fn test_persist_restore_and_clear() {
+        let file =
+            std::env::temp_dir().join(format!("edgefirst-imu-tare-{}.json", std::process::id()));
+        let args = Args::parse_from([
+            "edgefirst-imu",
+            "--tare",
+            "--tare-backend",
+            "host",
+            "--tare-file",
+            file.to_str().unwrap(),
+        ]);
+        let sample = Sample {
+            stamp: builtin_interfaces::Time::new(1, 0),
+            orientation: Quat::from_rpy(0.1, -0.2, 1.2),
+            angular_velocity: Vec3::default(),
+            linear_acceleration: Vec3::default(),
+            heading_accuracy: 0.0,
+        };
+
+        let mut tare = Tare::new(&args);
+        tare.apply(&sample);
+        tare.command("all").unwrap();
+        assert!(tare.command("persist").unwrap().persisted);
+
+        // A new instance restores the reference from the file.
+        let mut restored = Tare::new(&args);
+        let state = restored.state();
+        assert_eq!(state.mode, TareState::ALL);
+        assert!(state.persisted);
+        let q = restored.apply(&sample).orientation;
+        assert!((q.w.abs() - 1.0).abs() < 1e-9, "{q:?}");
+
+        let state = restored.command("clear").unwrap();
+        assert_eq!(state.mode, TareState::NONE);
+        assert!(!state.persisted);
+        assert!(!file.exists());
+        assert_eq!(restored.apply(&sample).orientation, sample.orientation);
+        // Clearing again is not an error, and nothing is restored.
+        restored.command("clear").unwrap();
+        assert_eq!(Tare::new(&args).state().mode, TareState::NONE);
+    }
+
+    #[test]
+    fn test_sensor_tare_state() {
+        let args = Args::parse_from(["edgefirst-imu", "--tare"]);
+        let mut tare = Tare::new(&args);
+        tare.sensor_applied(TareAction::Yaw);
+        assert_eq!(tare.state().mode, TareState::YAW);
+        tare.sensor_reset();
+        assert_eq!(tare.state().mode, TareState::NONE);
+
+        tare.sensor_applied(TareAction::All);
+        tare.sensor_applied(TareAction::Persist);
+        tare.sensor_reset();
+        let state = tare.state();
+        assert_eq!(state.mode, TareState::ALL);
+        assert!(state.persisted);
+        // The sensor applies the tare, the relative output is unchanged.
+        assert_eq!(state.offset.w, 1.0);
+
+        tare.sensor_applied(TareAction::Clear);
+        tare.sensor_reset();
+        let state = tare.state();
+        assert_eq!(state.mode, TareState::NONE);
+        assert!(!state.persisted);
+    }
+
+    #[test]
+    fn test_sensor_query_dropped() {
+        let args = Args::parse_from(["edgefirst-imu", "--tare"]);
+        let tare = Mutex::new(Tare::new(&args));
+        // No polling loop takes the command.
+        assert!(Tare::query(&tare, "all").is_err());
+        assert!(tare.lock().unwrap().take_pending().is_empty());
+
+        // A reset drops the queued command and fails the query at once.
+        std::thread::scope(|scope| {
+            let query = scope.spawn(|| Tare::query(&tare, "yaw"));
+            while tare.lock().unwrap().pending.is_empty() {
+                std::thread::yield_now();
+            }
+            tare.lock().unwrap().sensor_reset();
+            assert!(query.join().unwrap().is_err());
+        });
+        assert!(tare.lock().unwrap().take_pending().is_empty());
+        assert_eq!(tare.lock().unwrap().state().mode, TareState::NONE);
+    }
+
+    #[test]
+    fn test_check_backend() {
+        let parse =
+            |extra: &[&str]| Args::parse_from(["edgefirst-imu", "--tare"].iter().chain(extra));
+        assert!(check_backend(&parse(&[])).is_ok());
+        assert!(check_backend(&parse(&["--fusion", "ekf", "--fusion-primary"])).is_err());
+        assert!(check_backend(&parse(&["--fusion", "ekf"])).is_ok());
+        assert!(check_backend(&parse(&["--mag-monitor", "--mag-fallback"])).is_err());
+        assert!(check_backend(&parse(&[
+            "--fusion",
+            "ekf",
+            "--fusion-primary",
+            "--tare-backend",
+            "host"
+        ]))
+        .is_ok());
+    }
+}
@@ -0,0 +1,307 @@
+// Copyright 2025 Au-Zone Technologies Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Vibration spectrum analysis.
+//!
+//! The power spectral density of each accelerometer axis is estimated with
+//! Welch's method: segments of the window size overlapping by half are
+//! detrended, Hann windowed and transformed, and their periodograms are
+//! averaged over each publish period. The density is summed over the
+//! configured bands, giving the acceleration variance in each band, and the
+//! strongest bin above DC is reported as the peak.
+use crate::{
+    args::Args,
+    math::Vec3,
+    msgs::{VibrationAxis, VibrationSpectrum},
+    sample::Sample,
+};
+use edgefirst_schemas::std_msgs;
+use std::{f64::consts::PI, str::FromStr};
+
+/// Frequency band in Hz, given as `low-high`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Band {
+    pub low: f64,
+    pub high: f64,
+}
+
+impl FromStr for Band {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (low, high) = s
+            .split_once('-')
+            .ok_or_else(|| format!("expected low-high in {s:?}"))?;
+        let parse = |v: &str| {
+            v.trim()
+                .parse::<f64>()
+                .map_err(|e| format!("invalid frequency in {s:?}: {e}"))
+        };
+        let (low, high) = (parse(low)?, parse(high)?);
+        if low < 0.0 || high <= low {
+            return Err(format!("band {s:?} must satisfy 0 <= low < high"));
+        }
+        Ok(Self { low, high })
+    }
+}
+
+/// Parses the window size, which must be a power of two for the FFT.
+pub fn window_size(s: &str) -> Result<usize, String> {
+    let size = s.parse::<usize>().map_err(|e| e.to_string())?;
+    if size < 16 || !size.is_power_of_two() {
+        return Err(format!("{size} is not a power of two of at least 16"));
+    }
+    Ok(size)
+}
+
+/// Checks that every sample carries a fresh accelerometer reading. The
+/// accelerometer is held between its reports, so a slower report would be
+/// analyzed as a staircase at the sample rate.
+pub fn check_rates(args: &Args) -> Result<(), String> {
+    if args.vibration && args.accel_interval != args.sample_interval {
+        return Err(format!(
+            "--vibration needs --accel-interval equal to --sample-interval \
+             ({} ms, expected {} ms)",
+            args.accel_interval, args.sample_interval
+        ));
+    }
+    Ok(())
+}
+
+pub struct VibrationAnalyzer {
+    frame_id: String,
+    bands: Vec<Band>,
+    /// Publish period in nanoseconds.
+    period: u64,
+    window: Vec<f64>,
+
+    /// `(stamp, acceleration)` of the current segment.
+    segment: Vec<(u64, Vec3)>,
+    /// Summed periodograms of each axis and summed sample rates.
+    power: [Vec<f64>; 3],
+    rate: f64,
+    segments: u32,
+    start: Option<u64>,
+}
+
+impl VibrationAnalyzer {
+    pub fn new(args: &Args) -> Self {
+        let size = args.vibration_window;
+        Self {
+            frame_id: args.frame_id.clone(),
+            bands: args.vibration_bands.clone(),
+            period: (args.vibration_period * 1e9) as u64,
+            // Periodic Hann window.
+            window: (0..size)
+                .map(|i| 0.5 - 0.5 * (2.0 * PI * i as f64 / size as f64).cos())
+                .collect(),
+            segment: Vec::with_capacity(size),
+            power: std::array::from_fn(|_| vec![0.0; size / 2 + 1]),
+            rate: 0.0,
+            segments: 0,
+            start: None,
+        }
+    }
+
+    /// Adds a sample and returns the spectrum at the end of each period.
+    pub fn push(&mut self, sample: &Sample) -> Option<VibrationSpectrum> {
+        let stamp = sample.stamp.to_nanos();
+        let start = *self.start.get_or_insert(stamp);
+        self.segment.push((stamp, sample.linear_acceleration));
+        if self.segment.len() == self.window.len() {
+            self.analyze_segment();
+            // Half overlap between segments.
+            self.segment.drain(..self.window.len() / 2);
+        }
+
+        if stamp.saturating_sub(start) < self.period || self.segments == 0 {
+            return None;
+        }
+        let spectrum = self.spectrum(sample);
+        self.start = Some(stamp);
+        self.segments = 0;
+        self.rate = 0.0;
+        for power in &mut self.power {
+            power.fill(0.0);
+        }
+        Some(spectrum)
+    }
+
+    fn analyze_segment(&mut self) {
+        let n = self.segment.len();
+        let duration = self.segment[n - 1].0.saturating_sub(self.segment[0].0) as f64 * 1e-9;
+        if duration <= 0.0 {
+            return;
+        }
+        // Measured rather than nominal rate, which the sensor only
+        // approximates.
+        self.rate += (n - 1) as f64 / duration;
+        self.segments += 1;
+
+        for axis in 0..3 {
+            let values: Vec<f64> = self
+                .segment
+                .iter()
+                .map(|(_, v)| [v.x, v.y, v.z][axis])
+                .collect();
+            let mean = values.iter().sum::<f64>() / n as f64;
+            let mut re: Vec<f64> = values
+                .iter()
+                .zip(&self.window)
+                .map(|(v, w)| (v - mean) * w)
+                .collect();
+            let mut im = vec![0.0; n];
+            fft(&mut re, &mut im);
+            for (k, power) in self.power[axis].iter_mut().enumerate() {
+                *power += re[k] * re[k] + im[k] * im[k];
+            }
+        }
+    }
+
+    fn spectrum(&self, sample: &Sample) -> VibrationSpectrum {
+        let n = self.window.len();
+        let rate = self.rate / self.segments as f64;
+        VibrationSpectrum {
+            header: std_msgs::Header {
+                stamp: sample.stamp.clone(),
+                frame_id: self.frame_id.clone(),
+            },
+            sample_rate: rate,
+            resolution: rate / n as f64,
+            window_size: n as u32,
+            segments: self.segments,
+            band_low: self.bands.iter().map(|b| b.low).collect(),
+            band_high: self.bands.iter().map(|b| b.high).collect(),
+            x: self.axis(&self.power[0], rate),
+            y: self.axis(&self.power[1], rate),
+            z: self.axis(&self.power[2], rate),
+        }
+    }
+
+    fn axis(&self, power: &[f64], rate: f64) -> VibrationAxis {
+        let n = self.window.len();
+        let resolution = rate / n as f64;
+        let window_power = self.window.iter().map(|w| w * w).sum::<f64>();
+        // One-sided density in (m/s²)²/Hz.
+        let density: Vec<f64> = power
+            .iter()
+            .enumerate()
+            .map(|(k, p)| {
+                let one_sided = if k == 0 || k == n / 2 { 1.0 } else { 2.0 };
+                one_sided * p / (self.segments as f64 * rate * window_power)
+            })
+            .collect();
+
+        let band_energy = self
+            .bands
+            .iter()
+            .map(|band| {
+                density
+                    .iter()
+                    .enumerate()
+                    .filter(|(k, _)| (band.low..band.high).contains(&(*k as f64 * resolution)))
+                    .map(|(_, d)| d * resolution)
+                    .sum()
+            })
+            .collect();
+        let (peak, peak_density) = density
+            .iter()
+            .enumerate()
+            .skip(1)
+            .max_by(|a, b| a.1.total_cmp(b.1))
+            .map_or((0, 0.0), |(k, d)| (k, *d));
+
+        VibrationAxis {
+            rms: (density.iter().skip(1).sum::<f64>() * resolution).sqrt(),
+            band_energy,
+            peak_frequency: peak as f64 * resolution,
+            peak_density,
+        }
+    }
+}
+
+/// In-place iterative radix-2 FFT. The length must be a power of two.
+fn fft(re: &mut [f64], im: &mut [f64]) {
+    let n = re.len();
+    let mut j = 0;
+    for i in 1..n {
+        let mut bit = n >> 1;
+        while j & bit != 0 {
+            j ^= bit;
+            bit >>= 1;
+        }
+        j |= bit;
+        if i < j {
+            re.swap(i, j);
+            im.swap(i, j);
+        }
+    }
+
+    let mut len = 2;
+    while len <= n {
+        let angle = -2.0 * PI / len as f64;
+        for start in (0..n).step_by(len) {
+            for k in 0..len / 2 {
+                let (sin, cos) = (angle * k as f64).sin_cos();
+                let (a, b) = (start + k, start + k + len / 2);
+                let tr = re[b] * cos - im[b] * sin;
+                let ti = re[b] * sin + im[b] * cos;
+                re[b] = re[a] - tr;
+                im[b] = im[a] - ti;
+                re[a] += tr;
+                im[a] += ti;
+            }
+        }
+        len <<= 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::math::Quat;
+    use clap::Parser;
+    use edgefirst_schemas::builtin_interfaces;
+
+    #[test]
+    fn test_sine_band_energy_and_peak() {
+        let args = Args::parse_from(["edgefirst-imu", "--vibration"]);
+        let mut analyzer = VibrationAnalyzer::new(&args);
+        let mut spectrum = None;
+        for i in 0..400u64 {
+            let t = i as f64 / 200.0;
+            let sample = Sample {
+                stamp: builtin_interfaces::Time::from_nanos(1_000_000_000 + i * 5_000_000),
+                orientation: Quat::new(1.0, 0.0, 0.0, 0.0),
+                angular_velocity: Vec3::default(),
+                // 40 Hz at 1 m/s² on X over gravity on Z.
+                linear_acceleration: Vec3::new((2.0 * PI * 40.0 * t).sin(), 0.0, 9.81),
+                heading_accuracy: 0.0,
+            };
+            spectrum = analyzer.push(&sample).or(spectrum);
+        }
+
+        let spectrum = spectrum.unwrap();
+        assert!((spectrum.sample_rate - 200.0).abs() < 1e-6);
+        assert!((spectrum.x.peak_frequency - 40.0).abs() <= spectrum.resolution);
+        // A unit sine has a variance of 0.5, all within 10-50 Hz.
+        assert!(
+            (spectrum.x.band_energy[1] - 0.5).abs() < 0.01,
+            "{:?}",
+            spectrum.x
+        );
+        assert!(spectrum.x.band_energy[0] < 1e-3);
+        assert!((spectrum.x.rms - 0.5f64.sqrt()).abs() < 0.01);
+        assert!(spectrum.z.rms < 1e-9);
+    }
+
+    #[test]
+    fn test_requires_accel_at_sample_rate() {
+        let args = Args::parse_from(["edgefirst-imu", "--vibration", "--accel-interval", "20"]);
+        assert!(check_rates(&args).is_err());
+        let args = Args::parse_from(["edgefirst-imu", "--vibration", "--accel-interval", "5"]);
+        assert!(check_rates(&args).is_ok());
+        let args = Args::parse_from(["edgefirst-imu", "--accel-interval", "20"]);
+        assert!(check_rates(&args).is_ok());
+    }
+}
@@ -0,0 +1,411 @@
+// Copyright 2025 Au-Zone Technologies Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Wheel odometry fusion.
+//!
+//! An extended Kalman filter with the state `[x, y, z, yaw, speed, yaw
+//! rate]` in the world frame of the output convention. Every IMU sample
+//! predicts the state with a constant speed and yaw rate model and updates
+//! it with the measured yaw rate and the orientation heading; every wheel
+//! odometry message updates the speed and yaw rate. The travel direction is
+//! the heading, tilted by the IMU pitch in 3D mode so the position follows
+//! slopes, and level in 2D mode.
+//!
+//! All measurements observe a single state, so each update is a scalar
+//! Kalman update on that state.
+use crate::{
+    args::Args,
+    convention::Convention,
+    math::{Quat, Vec3},
+    msgs::{Covariance, Odometry, PoseWithCovariance, TwistWithCovariance},
+    sample::{quaternion, vector3, Sample},
+};
+use clap::ValueEnum;
+use edgefirst_schemas::{builtin_interfaces, geometry_msgs, serde_cdr, std_msgs};
+use log::{debug, info, warn};
+use std::f64::consts::PI;
+
+/// Speed change in m/s²/√Hz and yaw rate change in rad/s²/√Hz driving the
+/// process noise.
+const ACCEL_NOISE: f64 = 0.5;
+const YAW_ACCEL_NOISE: f64 = 1.0;
+
+/// Standard deviation of the gyroscope yaw rate in rad/s.
+const GYRO_NOISE: f64 = 0.01;
+
+/// Lower bound of the orientation heading standard deviation in rad.
+const MIN_HEADING_NOISE: f64 = 0.035;
+
+/// Nanoseconds without wheel odometry after which the output stops.
+const WHEEL_TIMEOUT: u64 = 1_000_000_000;
+
+/// Variance of the dimensions the filter does not estimate, large so that
+/// consumers ignore them.
+const UNUSED_VARIANCE: f64 = 1e6;
+
+const X: usize = 0;
+const Y: usize = 1;
+const Z: usize = 2;
+const YAW: usize = 3;
+const SPEED: usize = 4;
+const YAW_RATE: usize = 5;
+
+type Mat6 = [[f64; 6]; 6];
+
+/// Message carrying the wheel odometry.
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum WheelSource {
+    /// `nav_msgs/Odometry` with the body frame twist.
+    #[default]
+    Odometry,
+    /// `geometry_msgs/TwistStamped` with the body frame twist.
+    Twist,
+}
+
+impl WheelSource {
+    /// Decodes a message into its timestamp, the forward speed in m/s and
+    /// the yaw rate in rad/s, both in the ROS body frame (forward, left,
+    /// up).
+    pub fn decode(&self, payload: &[u8]) -> Result<(builtin_interfaces::Time, f64, f64), String> {
+        let (stamp, twist) = match self {
+            Self::Odometry => {
+                let msg = serde_cdr::deserialize::<Odometry>(payload).map_err(|e| e.to_string())?;
+                (msg.header.stamp, msg.twist.twist)
+            }
+            Self::Twist => {
+                let msg = serde_cdr::deserialize::<geometry_msgs::TwistStamped>(payload)
+                    .map_err(|e| e.to_string())?;
+                (msg.header.stamp, msg.twist)
+            }
+        };
+        Ok((stamp, twist.linear.x, twist.angular.z))
+    }
+}
+
+/// Dimensions of the fused odometry.
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum WheelMode {
+    /// Position in the horizontal plane and heading only.
+    #[default]
+    #[value(name = "2d")]
+    Planar,
+    /// Position follows the IMU pitch and the orientation keeps the IMU
+    /// roll and pitch.
+    #[value(name = "3d")]
+    Spatial,
+}
+
+pub struct WheelOdometry {
+    odom_frame: String,
+    frame_id: String,
+    mode: WheelMode,
+    convention: Convention,
+    speed_noise: f64,
+    yaw_rate_noise: f64,
+    /// Publish period in nanoseconds.
+    period: u64,
+
+    state: [f64; 6],
+    covariance: Mat6,
+    /// Yaw of the orientation is initialized from the first sample.
+    initialized: bool,
+    stamp: Option<u64>,
+    last_wheel: Option<u64>,
+    last_publish: Option<u64>,
+}
+
+impl WheelOdometry {
+    pub fn new(args: &Args) -> Self {
+        let mut covariance = [[0.0; 6]; 6];
+        covariance[YAW][YAW] = PI * PI;
+        covariance[SPEED][SPEED] = 100.0;
+        covariance[YAW_RATE][YAW_RATE] = 1.0;
+        Self {
+            odom_frame: args.odom_frame.clone(),
+            frame_id: args.frame_id.clone(),
+            mode: args.wheel_mode,
+            convention: args.convention,
+            speed_noise: args.wheel_speed_noise,
+            yaw_rate_noise: args.wheel_yaw_rate_noise,
+            period: if args.rate > 0.0 {
+                (1e9 / args.rate).round() as u64
+            } else {
+                0
+            },
+            state: [0.0; 6],
+            covariance,
+            initialized: false,
+            stamp: None,
+            last_wheel: None,
+            last_publish: None,
+        }
+    }
+
+    /// Fuses a wheel odometry forward speed and yaw rate in the ROS body
+    /// frame, measured at `stamp`. Messages are aged by their stamp, or by
+    /// their arrival when they carry none, and ignored once older than the
+    /// wheel timeout.
+    pub fn measure(&mut self, stamp: &builtin_interfaces::Time, speed: f64, yaw_rate: f64) {
+        let Some(now) = self.stamp else {
+            return;
+        };
+        let stamp = match stamp.to_nanos() {
+            0 => now,
+            stamp => stamp,
+        };
+        if now.saturating_sub(stamp) > WHEEL_TIMEOUT {
+            debug!("Ignoring stale wheel odometry");
+            return;
+        }
+        if self
+            .last_wheel
+            .is_none_or(|t| stamp.saturating_sub(t) > WHEEL_TIMEOUT)
+        {
+            info!("Fusing wheel odometry");
+        }
+        self.last_wheel = Some(self.last_wheel.map_or(stamp, |t| t.max(stamp)));
+        // The yaw rate is about the body up axis, which points down in FRD.
+        let yaw_rate = match self.convention {
+            Convention::Enu => yaw_rate,
+            Convention::Ned => -yaw_rate,
+        };
+        self.update(SPEED, speed, self.speed_noise);
+        self.update(YAW_RATE, yaw_rate, self.yaw_rate_noise);
+    }
+
+    /// Predicts the state to an output frame sample and fuses its yaw rate
+    /// and heading. Returns the odometry when it is due at the --rate of
+    /// the IMU output.
+    pub fn update_imu(&mut self, sample: &Sample) -> Option<Odometry> {
+        let stamp = sample.stamp.to_nanos();
+        let forward = sample.orientation.rotate(Vec3::new(1.0, 0.0, 0.0));
+        let heading = forward.y.atan2(forward.x);
+        let yaw_rate = sample.orientation.rotate(sample.angular_velocity).z;
+
+        if !self.initialized {
+            self.initialized = true;
+            self.state[YAW] = heading;
+        }
+        if let Some(previous) = self.stamp.replace(stamp) {
+            let dt = stamp.saturating_sub(previous) as f64 * 1e-9;
+            self.predict(forward, dt);
+        }
+        self.update(YAW_RATE, yaw_rate, GYRO_NOISE);
+        self.update(YAW, heading, sample.heading_accuracy.max(MIN_HEADING_NOISE));
+
+        if self
+            .last_wheel
+            .is_none_or(|t| stamp.saturating_sub(t) > WHEEL_TIMEOUT)
+        {
+            if self.last_wheel.take().is_some() {
+                warn!("No wheel odometry for 1 s, fused odometry suspended");
+            }
+            return None;
+        }
+        if self
+            .last_publish
+            .is_some_and(|t| stamp.saturating_sub(t) < self.period)
+        {
+            return None;
+        }
+        self.last_publish = Some(stamp);
+        Some(self.odometry(&sample.stamp, sample.orientation, heading))
+    }
+
+    fn predict(&mut self, forward: Vec3, dt: f64) {
+        let (sin, cos) = self.state[YAW].sin_cos();
+        let (horizontal, vertical) = match self.mode {
+            WheelMode::Planar => (1.0, 0.0),
+            WheelMode::Spatial => (forward.x.hypot(forward.y), forward.z),
+        };
+        let speed = self.state[SPEED];
+
+        self.state[X] += horizontal * cos * speed * dt;
+        self.state[Y] += horizontal * sin * speed * dt;
+        self.state[Z] += vertical * speed * dt;
+        self.state[YAW] = wrap(self.state[YAW] + self.state[YAW_RATE] * dt);
+
+        let mut f: Mat6 = std::array::from_fn(|i| std::array::from_fn(|j| f64::from(i == j)));
+        f[X][YAW] = -horizontal * sin * speed * dt;
+        f[X][SPEED] = horizontal * cos * dt;
+        f[Y][YAW] = horizontal * cos * speed * dt;
+        f[Y][SPEED] = horizontal * sin * dt;
+        f[Z][SPEED] = vertical * dt;
+        f[YAW][YAW_RATE] = dt;
+
+        let fp: Mat6 = std::array::from_fn(|i| {
+            std::array::from_fn(|j| (0..6).map(|k| f[i][k] * self.covariance[k][j]).sum())
+        });
+        self.covariance = std::array::from_fn(|i| {
+            std::array::from_fn(|j| (0..6).map(|k| fp[i][k] * f[j][k]).sum())
+        });
+        self.covariance[SPEED][SPEED] += ACCEL_NOISE * ACCEL_NOISE * dt;
+        self.covariance[YAW_RATE][YAW_RATE] += YAW_ACCEL_NOISE * YAW_ACCEL_NOISE * dt;
+    }
+
+    /// Scalar update of state `index` with a measurement of standard
+    /// deviation `noise`.
+    fn update(&mut self, index: usize, measured: f64, noise: f64) {
+        let mut innovation = measured - self.state[index];
+        if index == YAW {
+            innovation = wrap(innovation);
+        }
+        let variance = self.covariance[index][index] + noise * noise;
+        let gain: [f64; 6] = std::array::from_fn(|i| self.covariance[i][index] / variance);
+        let row = self.covariance[index];
+        for (i, k) in gain.iter().enumerate() {
+            self.state[i] += k * innovation;
+            for (p, r) in self.covariance[i].iter_mut().zip(row) {
+                *p -= k * r;
+            }
+        }
+        self.state[YAW] = wrap(self.state[YAW]);
+    }
+
+    fn odometry(
+        &self,
+        stamp: &builtin_interfaces::Time,
+        orientation: Quat,
+        heading: f64,
+    ) -> Odometry {
+        let p = &self.covariance;
+        let yaw = Quat::from_rpy(0.0, 0.0, self.state[YAW]);
+        let (orientation, z, z_variance) = match self.mode {
+            WheelMode::Planar => (yaw, 0.0, UNUSED_VARIANCE),
+            WheelMode::Spatial => (
+                // Replace the IMU heading with the fused one.
+                (Quat::from_rpy(0.0, 0.0, self.state[YAW] - heading) * orientation).normalize(),
+                self.state[Z],
+                p[Z][Z],
+            ),
+        };
+
+        let mut pose = Covariance([0.0; 36]);
+        for (i, a) in [(0, X), (1, Y), (5, YAW)] {
+            for (j, b) in [(0, X), (1, Y), (5, YAW)] {
+                pose.0[i * 6 + j] = p[a][b];
+            }
+        }
+        pose.0[2 * 7] = z_variance;
+        pose.0[3 * 7] = UNUSED_VARIANCE;
+        pose.0[4 * 7] = UNUSED_VARIANCE;
+
+        let mut twist = Covariance([0.0; 36]);
+        for i in 0..6 {
+            twist.0[i * 7] = UNUSED_VARIANCE;
+        }
+        twist.0[0] = p[SPEED][SPEED];
+        twist.0[35] = p[YAW_RATE][YAW_RATE];
+
+        Odometry {
+            header: std_msgs::Header {
+                stamp: stamp.clone(),
+                frame_id: self.odom_frame.clone(),
+            },
+            child_frame_id: self.frame_id.clone(),
+            pose: PoseWithCovariance {
+                pose: geometry_msgs::Pose {
+                    position: geometry_msgs::Point {
+                        x: self.state[X],
+                        y: self.state[Y],
+                        z,
+                    },
+                    orientation: quaternion(orientation),
+                },
+                covariance: pose,
+            },
+            twist: TwistWithCovariance {
+                twist: geometry_msgs::Twist {
+                    linear: vector3(Vec3::new(self.state[SPEED], 0.0, 0.0)),
+                    angular: vector3(Vec3::new(0.0, 0.0, self.state[YAW_RATE])),
+                },
+                covariance: twist,
+            },
+        }
+    }
+}
+
+/// Wraps an angle in radians to `[-π, π)`.
+fn wrap(radians: f64) -> f64 {
+    (radians + PI).rem_euclid(2.0 * PI) - PI
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+
+    #[test]
+    fn test_follows_heading_and_turn() {
+        let args = Args::parse_from(["edgefirst-imu", "--wheel-topic", "rt/odom"]);
+        let mut odometry = WheelOdometry::new(&args);
+        let mut sample = Sample {
+            stamp: builtin_interfaces::Time::new(0, 0),
+            orientation: Quat::from_rpy(0.0, 0.0, 30f64.to_radians()),
+            angular_velocity: Vec3::default(),
+            linear_acceleration: Vec3::new(0.0, 0.0, 9.81),
+            heading_accuracy: 0.0,
+        };
+
+        // One second straight at 1 m/s, heading 30 degrees from east.
+        let mut msg = None;
+        for i in 0..=200 {
+            sample.stamp = builtin_interfaces::Time::from_nanos(1_000_000_000 + i * 5_000_000);
+            msg = odometry.update_imu(&sample);
+            if i % 4 == 0 {
+                odometry.measure(&sample.stamp, 1.0, 0.0);
+            }
+        }
+        let position = msg.unwrap().pose.pose.position;
+        // The speed converges from zero over the first wheel messages.
+        assert!((position.x - 0.866).abs() < 0.03, "{}", position.x);
+        assert!((position.y - 0.5).abs() < 0.02, "{}", position.y);
+
+        // Turn left at 0.5 rad/s for a second.
+        let mut msg = None;
+        for i in 1..=200 {
+            let yaw = 30f64.to_radians() + 0.5 * i as f64 * 0.005;
+            sample.orientation = Quat::from_rpy(0.0, 0.0, yaw);
+            sample.angular_velocity = Vec3::new(0.0, 0.0, 0.5);
+            sample.stamp = builtin_interfaces::Time::from_nanos(2_000_000_000 + i * 5_000_000);
+            msg = odometry.update_imu(&sample).or(msg);
+            if i % 4 == 0 {
+                odometry.measure(&sample.stamp, 1.0, 0.5);
+            }
+        }
+        let msg = msg.unwrap();
+        assert!((msg.twist.twist.angular.z - 0.5).abs() < 0.01);
+        let q = msg.pose.pose.orientation;
+        let yaw = 2.0 * q.z.atan2(q.w);
+        assert!((yaw - (30f64.to_radians() + 0.5)).abs() < 0.01, "{yaw}");
+    }
+
+    #[test]
+    fn test_ages_wheel_messages_by_stamp() {
+        let args = Args::parse_from(["edgefirst-imu", "--wheel-topic", "rt/odom"]);
+        let mut odometry = WheelOdometry::new(&args);
+        let mut sample = Sample {
+            stamp: builtin_interfaces::Time::new(10, 0),
+            orientation: Quat::new(1.0, 0.0, 0.0, 0.0),
+            angular_velocity: Vec3::default(),
+            linear_acceleration: Vec3::new(0.0, 0.0, 9.81),
+            heading_accuracy: 0.0,
+        };
+        odometry.update_imu(&sample);
+
+        // Delivered now but measured 2 s ago.
+        odometry.measure(&builtin_interfaces::Time::new(8, 0), 1.0, 0.0);
+        assert!(odometry.update_imu(&sample).is_none());
+
+        // Measured 0.5 s ago, so it expires 0.5 s after its arrival.
+        odometry.measure(&builtin_interfaces::Time::new(9, 500_000_000), 1.0, 0.0);
+        assert!(odometry.update_imu(&sample).is_some());
+        sample.stamp = builtin_interfaces::Time::new(10, 600_000_000);
+        assert!(odometry.update_imu(&sample).is_none());
+
+        // Without a stamp the arrival is used.
+        odometry.measure(&builtin_interfaces::Time::new(0, 0), 1.0, 0.0);
+        sample.stamp = builtin_interfaces::Time::new(11, 0);
+        assert!(odometry.update_imu(&sample).is_some());
+    }
+}
@@ -0,0 +1,309 @@
+// Copyright 2025 Au-Zone Technologies Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Offline World Magnetic Model (WMM2025).
+//!
+//! The spherical harmonic coefficients are embedded so the declination can
+//! be computed without network access. The model is valid from 2025.0 to
+//! 2030.0; dates outside that range are still evaluated using the secular
+//! variation terms but lose accuracy.
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Epoch of the embedded model in decimal years.
+pub const EPOCH: f64 = 2025.0;
+
+/// Maximum degree and order of the model.
+const DEGREE: usize = 12;
+
+/// Geomagnetic reference radius in kilometres.
+const REFERENCE_RADIUS: f64 = 6371.2;
+
+/// WGS84 semi-major axis in kilometres.
+const WGS84_A: f64 = 6378.137;
+
+/// WGS84 flattening.
+const WGS84_F: f64 = 1.0 / 298.257223563;
+
+/// WMM2025 coefficients: `(n, m, g, h, g_dot, h_dot)` with the main field
+/// in nT and the secular variation in nT/year.
+#[rustfmt::skip]
+const COEFFICIENTS: [(usize, usize, f64, f64, f64, f64); 90] = [
+    (1, 0, -29351.8, 0.0, 12.0, 0.0),
+    (1, 1, -1410.8, 4545.4, 9.7, -21.5),
+    (2, 0, -2556.6, 0.0, -11.6, 0.0),
+    (2, 1, 2951.1, -3133.6, -5.2, -27.7),
+    (2, 2, 1649.3, -815.1, -8.0, -12.1),
+    (3, 0, 1361.0, 0.0, -1.3, 0.0),
+    (3, 1, -2404.1, -56.6, -4.2, 4.0),
+    (3, 2, 1243.8, 237.5, 0.4, -0.3),
+    (3, 3, 453.6, -549.5, -15.6, -4.1),
+    (4, 0, 895.0, 0.0, -1.6, 0.0),
+    (4, 1, 799.5, 278.6, -2.4, -1.1),
+    (4, 2, 55.7, -133.9, -6.0, 4.1),
+    (4, 3, -281.1, 212.0, 5.6, 1.6),
+    (4, 4, 12.1, -375.6, -7.0, -4.4),
+    (5, 0, -233.2, 0.0, 0.6, 0.0),
+    (5, 1, 368.9, 45.4, 1.4, -0.5),
+    (5, 2, 187.2, 220.2, 0.0, 2.2),
+    (5, 3, -138.7, -122.9, 0.6, 0.4),
+    (5, 4, -142.0, 43.0, 2.2, 1.7),
+    (5, 5, 20.9, 106.1, 0.9, 1.9),
+    (6, 0, 64.4, 0.0, -0.2, 0.0),
+    (6, 1, 63.8, -18.4, -0.4, 0.3),
+    (6, 2, 76.9, 16.8, 0.9, -1.6),
+    (6, 3, -115.7, 48.8, 1.2, -0.4),
+    (6, 4, -40.9, -59.8, -0.9, 0.9),
+    (6, 5, 14.9, 10.9, 0.3, 0.7),
+    (6, 6, -60.7, 72.7, 0.9, 0.9),
+    (7, 0, 79.5, 0.0, -0.0, 0.0),
+    (7, 1, -77.0, -48.9, -0.1, 0.6),
+    (7, 2, -8.8, -14.4, -0.1, 0.5),
+    (7, 3, 59.3, -1.0, 0.5, -0.8),
+    (7, 4, 15.8, 23.4, -0.1, 0.0),
+    (7, 5, 2.5, -7.4, -0.8, -1.0),
+    (7, 6, -11.1, -25.1, -0.8, 0.6),
+    (7, 7, 14.2, -2.3, 0.8, -0.2),
+    (8, 0, 23.2, 0.0, -0.1, 0.0),
+    (8, 1, 10.8, 7.1, 0.2, -0.2),
+    (8, 2, -17.5, -12.6, 0.0, 0.5),
+    (8, 3, 2.0, 11.4, 0.5, -0.4),
+    (8, 4, -21.7, -9.7, -0.1, 0.4),
+    (8, 5, 16.9, 12.7, 0.3, -0.5),
+    (8, 6, 15.0, 0.7, 0.2, -0.6),
+    (8, 7, -16.8, -5.2, -0.0, 0.3),
+    (8, 8, 0.9, 3.9, 0.2, 0.2),
+    (9, 0, 4.6, 0.0, -0.0, 0.0),
+    (9, 1, 7.8, -24.8, -0.1, -0.3),
+    (9, 2, 3.0, 12.2, 0.1, 0.3),
+    (9, 3, -0.2, 8.3, 0.3, -0.3),
+    (9, 4, -2.5, -3.3, -0.3, 0.3),
+    (9, 5, -13.1, -5.2, 0.0, 0.2),
+    (9, 6, 2.4, 7.2, 0.3, -0.1),
+    (9, 7, 8.6, -0.6, -0.1, -0.2),
+    (9, 8, -8.7, 0.8, 0.1, 0.4),
+    (9, 9, -12.9, 10.0, -0.1, 0.1),
+    (10, 0, -1.3, 0.0, 0.1, 0.0),
+    (10, 1, -6.4, 3.3, 0.0, 0.0),
+    (10, 2, 0.2, 0.0, 0.1, -0.0),
+    (10, 3, 2.0, 2.4, 0.1, -0.2),
+    (10, 4, -1.0, 5.3, -0.0, 0.1),
+    (10, 5, -0.6, -9.1, -0.3, -0.1),
+    (10, 6, -0.9, 0.4, 0.0, 0.1),
+    (10, 7, 1.5, -4.2, -0.1, 0.0),
+    (10, 8, 0.9, -3.8, -0.1, -0.1),
+    (10, 9, -2.7, 0.9, -0.0, 0.2),
+    (10, 10, -3.9, -9.1, -0.0, -0.0),
+    (11, 0, 2.9, 0.0, 0.0, 0.0),
+    (11, 1, -1.5, 0.0, -0.0, -0.0),
+    (11, 2, -2.5, 2.9, 0.0, 0.1),
+    (11, 3, 2.4, -0.6, 0.0, -0.0),
+    (11, 4, -0.6, 0.2, 0.0, 0.1),
+    (11, 5, -0.1, 0.5, -0.1, -0.0),
+    (11, 6, -0.6, -0.3, 0.0, -0.0),
+    (11, 7, -0.1, -1.2, -0.0, 0.1),
+    (11, 8, 1.1, -1.7, -0.1, -0.0),
+    (11, 9, -1.0, -2.9, -0.1, 0.0),
+    (11, 10, -0.2, -1.8, -0.1, 0.0),
+    (11, 11, 2.6, -2.3, -0.1, 0.0),
+    (12, 0, -2.0, 0.0, 0.0, 0.0),
+    (12, 1, -0.2, -1.3, 0.0, -0.0),
+    (12, 2, 0.3, 0.7, -0.0, 0.0),
+    (12, 3, 1.2, 1.0, -0.0, -0.1),
+    (12, 4, -1.3, -1.4, -0.0, 0.1),
+    (12, 5, 0.6, -0.0, -0.0, -0.0),
+    (12, 6, 0.6, 0.6, 0.1, -0.0),
+    (12, 7, 0.5, -0.1, -0.0, -0.0),
+    (12, 8, -0.1, 0.8, 0.0, 0.0),
+    (12, 9, -0.4, 0.1, 0.0, -0.0),
+    (12, 10, -0.2, -1.0, -0.1, -0.0),
+    (12, 11, -1.3, 0.1, -0.0, 0.0),
+    (12, 12, -0.7, 0.2, -0.1, -0.1),
+];
+
+/// Magnetic field elements at a location, in the local geodetic frame.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MagneticField {
+    /// Northward component in nT.
+    pub north: f64,
+    /// Eastward component in nT.
+    pub east: f64,
+    /// Downward component in nT.
+    pub down: f64,
+    /// Declination in degrees, east positive.
+    pub declination: f64,
+    /// Inclination (dip) in degrees, positive downward.
+    pub inclination: f64,
+    /// Total intensity in nT.
+    pub intensity: f64,
+}
+
+/// Evaluates the model at geodetic `latitude` and `longitude` in degrees,
+/// `altitude` in metres above the WGS84 ellipsoid and `year` as a decimal
+/// year.
+pub fn field(latitude: f64, longitude: f64, altitude: f64, year: f64) -> MagneticField {
+    let lat = latitude.to_radians();
+    let lon = longitude.to_radians();
+    let h = altitude / 1000.0;
+    let dt = year - EPOCH;
+
+    // Geodetic to geocentric spherical coordinates.
+    let e2 = WGS84_F * (2.0 - WGS84_F);
+    let rc = WGS84_A / (1.0 - e2 * lat.sin().powi(2)).sqrt();
+    let p = (rc + h) * lat.cos();
+    let z = (rc * (1.0 - e2) + h) * lat.sin();
+    let r = p.hypot(z);
+    let lat_c = (z / r).asin();
+
+    // Schmidt semi-normalised associated Legendre functions of
+    // sin(lat_c) and their derivatives with respect to colatitude.
+    let (x, s) = (lat_c.sin(), lat_c.cos());
+    let mut pnm = [[0.0; DEGREE + 1]; DEGREE + 1];
+    let mut dpnm = [[0.0; DEGREE + 1]; DEGREE + 1];
+    pnm[0][0] = 1.0;
+    for n in 1..=DEGREE {
+        let nf = n as f64;
+        for m in 0..=n {
+            let mf = m as f64;
+            if m == n {
+                let k = if n == 1 {
+                    1.0
+                } else {
+                    ((2.0 * nf - 1.0) / (2.0 * nf)).sqrt()
+                };
+                pnm[n][n] = k * s * pnm[n - 1][n - 1];
+                dpnm[n][n] = k * (s * dpnm[n - 1][n - 1] + x * pnm[n - 1][n - 1]);
+            } else {
+                let a = 2.0 * nf - 1.0;
+                let b = ((nf - 1.0).powi(2) - mf * mf).max(0.0).sqrt();
+                let c = (nf * nf - mf * mf).sqrt();
+                let (p2, dp2) = if n >= 2 {
+                    (pnm[n - 2][m], dpnm[n - 2][m])
+                } else {
+                    (0.0, 0.0)
+                };
+                pnm[n][m] = (a * x * pnm[n - 1][m] - b * p2) / c;
+                dpnm[n][m] = (a * (x * dpnm[n - 1][m] - s * pnm[n - 1][m]) - b * dp2) / c;
+            }
+        }
+    }
+
+    // Field in geocentric spherical coordinates.
+    let (mut bx, mut by, mut bz) = (0.0, 0.0, 0.0);
+    for &(n, m, g, hc, g_dot, h_dot) in COEFFICIENTS.iter() {
+        let g = g + dt * g_dot;
+        let hc = hc + dt * h_dot;
+        let ratio = (REFERENCE_RADIUS / r).powi(n as i32 + 2);
+        let (sin_ml, cos_ml) = (m as f64 * lon).sin_cos();
+        bx += ratio * (g * cos_ml + hc * sin_ml) * dpnm[n][m];
+        by += ratio * m as f64 * (g * sin_ml - hc * cos_ml) * pnm[n][m];
+        bz -= ratio * (n as f64 + 1.0) * (g * cos_ml + hc * sin_ml) * pnm[n][m];
+    }
+    // The east component is undefined at the geographic poles.
+    by /= s.max(1e-10);
+
+    // Rotate back to the geodetic frame.
+    let psi = lat_c - lat;
+    let north = bx * psi.cos() - bz * psi.sin();
+    let down = bx * psi.sin() + bz * psi.cos();
+    let east = by;
+
+    let horizontal = north.hypot(east);
+    MagneticField {
+        north,
+        east,
+        down,
+        declination: east.atan2(north).to_degrees(),
+        inclination: down.atan2(horizontal).to_degrees(),
+        intensity: horizontal.hypot(down),
+    }
+}
+
+/// Converts a civil date to a decimal year.
+pub fn decimal_year(year: i32, month: u32, day: u32) -> f64 {
+    let start = days_from_civil(year, 1, 1);
+    let length = days_from_civil(year + 1, 1, 1) - start;
+    year as f64 + (days_from_civil(year, month, day) - start) as f64 / length as f64
+}
+
+/// Current system date as a decimal year.
+pub fn current_year() -> f64 {
+    let secs = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs_f64())
+        .unwrap_or_default();
+    let days = (secs / 86400.0).floor() as i64;
+    let year = year_from_days(days);
+    let start = days_from_civil(year, 1, 1);
+    let length = days_from_civil(year + 1, 1, 1) - start;
+    year as f64 + (secs / 86400.0 - start as f64) / length as f64
+}
+
+/// Days since 1970-01-01 of a proleptic Gregorian date.
+fn days_from_civil(year: i32, month: u32, day: u32) -> i64 {
+    let y = if month <= 2 { year - 1 } else { year } as i64;
+    let era = y.div_euclid(400);
+    let yoe = y - era * 400;
+    let m = month as i64;
+    let doy = (153 * (if m > 2 { m - 3 } else { m + 9 }) + 2) / 5 + day as i64 - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146097 + doe - 719468
+}
+
+/// Gregorian year containing the given day since 1970-01-01.
+fn year_from_days(days: i64) -> i32 {
+    let mut year = (1970 + days / 365) as i32;
+    while days_from_civil(year, 1, 1) > days {
+        year -= 1;
+    }
+    while days_from_civil(year + 1, 1, 1) <= days {
+        year += 1;
+    }
+    year
+}
+
+/// Parses a `YYYY-MM-DD` date into a decimal year.
+pub fn parse_date(s: &str) -> Result<f64, String> {
+    let parts: Vec<_> = s.trim().split('-').collect();
+    let [year, month, day] = parts.as_slice() else {
+        return Err(format!("expected YYYY-MM-DD, got {s:?}"));
+    };
+    let year: i32 = year.parse().map_err(|e| format!("invalid year: {e}"))?;
+    let month: u32 = month.parse().map_err(|e| format!("invalid month: {e}"))?;
+    let day: u32 = day.parse().map_err(|e| format!("invalid day: {e}"))?;
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        return Err(format!("invalid date {s:?}"));
+    }
+    Ok(decimal_year(year, month, day))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_declination() {
+        // Published WMM2025 declinations at 2025.0, rounded to the degree.
+        let cases = [
+            (40.015, -105.27, 8.0), // Boulder
+            (-33.87, 151.21, 13.0), // Sydney
+            (35.68, 139.76, -8.0),  // Tokyo
+            (-33.92, 18.42, -26.0), // Cape Town
+        ];
+        for (lat, lon, expected) in cases {
+            let f = field(lat, lon, 0.0, EPOCH);
+            assert!(
+                (f.declination - expected).abs() < 1.5,
+                "({lat}, {lon}): {} != {expected}",
+                f.declination
+            );
+        }
+    }
+
+    #[test]
+    fn test_decimal_year() {
+        assert_eq!(decimal_year(2025, 1, 1), 2025.0);
+        assert!((decimal_year(2024, 7, 2) - 2024.5).abs() < 1e-9);
+        assert_eq!(parse_date("2025-01-01"), Ok(2025.0));
+        assert!(parse_date("2025-13-01").is_err());
+    }
+}
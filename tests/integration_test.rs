@@ -32,6 +32,9 @@ const COLLECTION_DURATION: Duration = Duration::from_secs(5);
 /// Topic the IMU service publishes to.
 const IMU_TOPIC: &str = "rt/imu";
 
+/// Default frame ID written to the IMU message header.
+const IMU_FRAME_ID: &str = "imu_link";
+
 /// Find the edgefirst-imu binary.
 /// In CI, it's passed via environment variable. Locally, look in target directory.
 fn find_imu_binary() -> String {
@@ -132,6 +135,8 @@ fn test_imu_publishing() {
             // Try to decode the message
             match serde_cdr::deserialize::<IMU>(&sample.payload().to_bytes()) {
                 Ok(imu) => {
+                    assert_eq!(imu.header.frame_id, IMU_FRAME_ID, "Unexpected IMU frame_id");
+
                     // Verify the message has reasonable values
                     // Quaternion should be normalized (magnitude ~= 1)
                     let mag = (imu.orientation.x.powi(2)